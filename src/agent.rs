@@ -0,0 +1,277 @@
+use std::ffi::OsString;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Environment variable overriding where cursor-agent is installed.
+pub const PATH_ENV: &str = "CURSOR_AGENT_PATH";
+
+const BINARY: &str = "cursor-agent";
+
+/// Everywhere the agent binary may be configured or installed, in the
+/// order they are tried.
+#[derive(Debug, Clone, Default)]
+pub struct Locations {
+    /// `--wrapper-agent-path`
+    pub flag: Option<String>,
+    /// `$CURSOR_AGENT_PATH`
+    pub env: Option<String>,
+    /// `[general] agent-path`
+    pub config: Option<String>,
+    /// `$PATH`
+    pub path: Option<OsString>,
+    pub home: Option<PathBuf>,
+    /// This wrapper's own binary, canonicalized. Skipped wherever it shows
+    /// up, e.g. when the wrapper is installed as `cursor-agent` on `$PATH`,
+    /// so it never runs itself.
+    pub this_exe: Option<PathBuf>,
+}
+
+impl Locations {
+    /// Locations of this process: its environment and home directory.
+    pub fn current(flag: Option<String>, config: Option<String>) -> Self {
+        Self {
+            flag,
+            env: std::env::var(PATH_ENV).ok(),
+            config,
+            path: std::env::var_os("PATH"),
+            home: dirs::home_dir(),
+            this_exe: std::env::current_exe().and_then(std::fs::canonicalize).ok(),
+        }
+    }
+
+    /// Every candidate path, labelled with where it came from.
+    fn candidates(&self) -> Vec<(&'static str, PathBuf)> {
+        let expand = |p: &str| match (p.strip_prefix("~/"), &self.home) {
+            (Some(rest), Some(home)) => home.join(rest),
+            _ => PathBuf::from(p),
+        };
+        let mut candidates = Vec::new();
+        let explicit = [
+            ("--wrapper-agent-path", &self.flag),
+            ("$CURSOR_AGENT_PATH", &self.env),
+            ("[general] agent-path", &self.config),
+        ];
+        for (source, path) in explicit {
+            if let Some(path) = path.as_deref().filter(|p| !p.is_empty()) {
+                candidates.push((source, expand(path)));
+            }
+        }
+        if let Some(ref path) = self.path {
+            for dir in std::env::split_paths(path).filter(|d| !d.as_os_str().is_empty()) {
+                candidates.push(("$PATH", dir.join(BINARY)));
+            }
+        }
+        if let Some(ref home) = self.home {
+            candidates.push(("default", home.join(".local/bin").join(BINARY)));
+        }
+        if let Some(ref this_exe) = self.this_exe {
+            candidates.retain(|(_, path)| std::fs::canonicalize(path).ok().as_ref() != Some(this_exe));
+        }
+        candidates
+    }
+
+    /// The first candidate that is an executable file, or an error listing
+    /// everything that was tried.
+    pub fn resolve(&self) -> Result<PathBuf, String> {
+        let candidates = self.candidates();
+        if let Some((_, path)) = candidates.iter().find(|(_, p)| is_executable(p)) {
+            return Ok(path.clone());
+        }
+        let mut msg = format!("{BINARY} not found. Tried:");
+        for (source, path) in &candidates {
+            msg.push_str(&format!("\n  {source}: {}", path.display()));
+        }
+        msg.push_str(&format!(
+            "\nInstall {BINARY}, or point {PATH_ENV}, [general] agent-path or \
+             --wrapper-agent-path at it."
+        ));
+        Err(msg)
+    }
+}
+
+/// Output of `<bin> --version`, trimmed; `None` if it fails or takes
+/// longer than a few seconds.
+pub async fn query_version(bin: &Path) -> Option<String> {
+    let output = tokio::process::Command::new(bin)
+        .arg("--version")
+        .stdin(std::process::Stdio::null())
+        .kill_on_drop(true)
+        .output();
+    let output = tokio::time::timeout(Duration::from_secs(5), output).await.ok()?.ok()?;
+    let text = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (output.status.success() && !text.is_empty()).then_some(text)
+}
+
+/// Numeric components of the first dotted version in `text`, e.g.
+/// `2025.09.18-7ae6800` -> `[2025, 9, 18]`.
+pub fn parse_version(text: &str) -> Option<Vec<u64>> {
+    let start = text.find(|c: char| c.is_ascii_digit())?;
+    let version: Vec<u64> = text[start..]
+        .split(|c: char| !c.is_ascii_digit() && c != '.')
+        .next()?
+        .split('.')
+        .map_while(|part| part.parse().ok())
+        .collect();
+    (!version.is_empty()).then_some(version)
+}
+
+/// Range of agent versions, written `<from>..<to>`: `from` is inclusive,
+/// `to` exclusive, and either may be left out (`..` is every version).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VersionRange {
+    from: Option<Vec<u64>>,
+    to: Option<Vec<u64>>,
+}
+
+impl VersionRange {
+    pub const ANY: VersionRange = VersionRange {
+        from: None,
+        to: None,
+    };
+
+    pub fn parse(range: &str) -> Result<Self, String> {
+        let (from, to) = range
+            .split_once("..")
+            .ok_or_else(|| format!("version range {range:?} must look like <from>..<to>"))?;
+        let bound = |s: &str| match s.trim() {
+            "" => Ok(None),
+            s => parse_version(s)
+                .map(Some)
+                .ok_or_else(|| format!("invalid version {s:?} in range {range:?}")),
+        };
+        Ok(Self {
+            from: bound(from)?,
+            to: bound(to)?,
+        })
+    }
+
+    /// Whether `version` is in the range. An unknown version is only in
+    /// a range without bounds.
+    pub fn contains(&self, version: Option<&[u64]>) -> bool {
+        let Some(version) = version else {
+            return *self == Self::ANY;
+        };
+        self.from.as_deref().is_none_or(|from| version >= from)
+            && self.to.as_deref().is_none_or(|to| version < to)
+    }
+}
+
+fn is_executable(path: &Path) -> bool {
+    path.metadata()
+        .is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!("ccw-{}-{name}", std::process::id()));
+            let _ = std::fs::remove_dir_all(&dir);
+            std::fs::create_dir_all(&dir).unwrap();
+            Self(dir)
+        }
+
+        fn executable(&self, rel: &str) -> PathBuf {
+            let path = self.0.join(rel);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, "#!/bin/sh\n").unwrap();
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+            path
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn first_existing_location_wins() {
+        let tmp = TempDir::new("agent-lookup");
+        let in_path = tmp.executable("bin/cursor-agent");
+        let default = tmp.executable(".local/bin/cursor-agent");
+        std::fs::write(tmp.0.join("not-executable"), "").unwrap();
+        let mut locations = Locations {
+            flag: None,
+            env: Some(tmp.0.join("missing").display().to_string()),
+            config: Some(tmp.0.join("not-executable").display().to_string()),
+            path: Some(tmp.0.join("bin").into_os_string()),
+            home: Some(tmp.0.clone()),
+            this_exe: None,
+        };
+        assert_eq!(locations.resolve().unwrap(), in_path);
+
+        locations.path = None;
+        assert_eq!(locations.resolve().unwrap(), default);
+
+        locations.flag = Some("~/.local/bin/cursor-agent".to_string());
+        assert_eq!(locations.resolve().unwrap(), default);
+    }
+
+    #[test]
+    fn own_binary_is_skipped() {
+        let tmp = TempDir::new("agent-self");
+        let wrapper = tmp.executable("bin/cursor-agent");
+        let default = tmp.executable(".local/bin/cursor-agent");
+        std::os::unix::fs::symlink(&wrapper, tmp.0.join("wrapper")).unwrap();
+        let locations = Locations {
+            flag: None,
+            env: Some(tmp.0.join("wrapper").display().to_string()),
+            config: None,
+            path: Some(tmp.0.join("bin").into_os_string()),
+            home: Some(tmp.0.clone()),
+            this_exe: Some(std::fs::canonicalize(&wrapper).unwrap()),
+        };
+        assert_eq!(locations.resolve().unwrap(), default);
+    }
+
+    #[test]
+    fn versions_and_ranges() {
+        assert_eq!(parse_version("2025.09.18-7ae6800"), Some(vec![2025, 9, 18]));
+        assert_eq!(parse_version("cursor-agent v1.4"), Some(vec![1, 4]));
+        assert_eq!(parse_version("unknown"), None);
+
+        let range = VersionRange::parse("2025.9..2025.11.2").unwrap();
+        assert!(range.contains(Some(&[2025, 9])));
+        assert!(range.contains(Some(&[2025, 10, 30])));
+        assert!(!range.contains(Some(&[2025, 11, 2])));
+        assert!(!range.contains(Some(&[2025, 8, 31])));
+        assert!(!range.contains(None));
+        assert!(VersionRange::parse("..").unwrap().contains(None));
+        assert!(VersionRange::parse("2026..").unwrap().contains(Some(&[2026, 1])));
+        assert!(VersionRange::parse("2025.9").is_err());
+        assert!(VersionRange::parse("x..").is_err());
+    }
+
+    #[test]
+    fn error_lists_every_location() {
+        let tmp = TempDir::new("agent-missing");
+        let locations = Locations {
+            flag: Some("/nowhere/agent".to_string()),
+            env: None,
+            config: Some("~/agent".to_string()),
+            path: Some(OsString::from("/a:/b")),
+            home: Some(tmp.0.clone()),
+            this_exe: None,
+        };
+        let err = locations.resolve().unwrap_err();
+        let home = tmp.0.display();
+        assert_eq!(
+            err.lines().take(6).collect::<Vec<_>>(),
+            [
+                "cursor-agent not found. Tried:".to_string(),
+                "  --wrapper-agent-path: /nowhere/agent".to_string(),
+                format!("  [general] agent-path: {home}/agent"),
+                "  $PATH: /a/cursor-agent".to_string(),
+                "  $PATH: /b/cursor-agent".to_string(),
+                format!("  default: {home}/.local/bin/cursor-agent"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,98 @@
+/// Prefix of the wrapper's own flags; everything else goes to cursor-agent.
+pub const PREFIX: &str = "--wrapper-";
+
+/// Options of the wrapper itself, given as `--wrapper-<flag>=<value>` or
+/// `--wrapper-<flag> <value>` anywhere on the command line before `--`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WrapperArgs {
+    /// `--wrapper-name`: session label, instead of the start directory name.
+    pub name: Option<String>,
+    /// `--wrapper-agent-path`: the cursor-agent binary to run.
+    pub agent_path: Option<String>,
+    /// `--wrapper-profile`: config profile to apply, instead of
+    /// `$CURSOR_WRAPPER_PROFILE`.
+    pub profile: Option<String>,
+}
+
+const USAGE: &str =
+    "wrapper flags: --wrapper-name=<name>, --wrapper-agent-path=<path>, --wrapper-profile=<profile>";
+
+impl WrapperArgs {
+    /// Split the command line into our flags and the arguments forwarded
+    /// to cursor-agent, which keep their order. From `--` on, everything
+    /// (the `--` included) is forwarded verbatim, so a prompt may start
+    /// with `--wrapper-`.
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<(Self, Vec<String>), String> {
+        let mut wrapper = Self::default();
+        let mut agent_args = Vec::new();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            if arg == "--" {
+                agent_args.push(arg);
+                agent_args.extend(args);
+                break;
+            }
+            let Some(flag) = arg.strip_prefix(PREFIX) else {
+                agent_args.push(arg);
+                continue;
+            };
+            let (flag, value) = match flag.split_once('=') {
+                Some((flag, value)) => (flag, value.to_string()),
+                None => {
+                    let value = args
+                        .next()
+                        .ok_or_else(|| format!("{arg} needs a value ({USAGE})"))?;
+                    (flag, value)
+                }
+            };
+            match flag {
+                "name" => wrapper.name = Some(value),
+                "agent-path" => wrapper.agent_path = Some(value),
+                "profile" => wrapper.profile = Some(value),
+                _ => return Err(format!("unknown flag {PREFIX}{flag} ({USAGE})")),
+            }
+        }
+        Ok((wrapper, agent_args))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<(WrapperArgs, Vec<String>), String> {
+        WrapperArgs::parse(args.iter().map(|s| s.to_string()))
+    }
+
+    #[test]
+    fn wrapper_flags_are_split_from_agent_args() {
+        let (wrapper, agent) = parse(&["--model", "gpt-5", "--wrapper-name=review", "fix it"]).unwrap();
+        assert_eq!(wrapper.name.as_deref(), Some("review"));
+        assert_eq!(agent, ["--model", "gpt-5", "fix it"]);
+
+        let (wrapper, agent) = parse(&["--wrapper-name", "api", "--resume", "--wrapper-agent-path=/opt/ca"]).unwrap();
+        assert_eq!(wrapper.name.as_deref(), Some("api"));
+        assert_eq!(wrapper.agent_path.as_deref(), Some("/opt/ca"));
+        assert_eq!(agent, ["--resume"]);
+
+        let (wrapper, agent) = parse(&["--wrapper-profile", "review", "-p"]).unwrap();
+        assert_eq!(wrapper.profile.as_deref(), Some("review"));
+        assert_eq!(agent, ["-p"]);
+    }
+
+    #[test]
+    fn arguments_after_double_dash_are_forwarded() {
+        let (wrapper, agent) =
+            parse(&["--wrapper-name=api", "--", "--wrapper-name=x", "--wrapper-profile"]).unwrap();
+        assert_eq!(wrapper.name.as_deref(), Some("api"));
+        assert_eq!(wrapper.profile, None);
+        assert_eq!(agent, ["--", "--wrapper-name=x", "--wrapper-profile"]);
+    }
+
+    #[test]
+    fn bad_wrapper_flags_are_errors() {
+        assert!(parse(&["--wrapper-name"]).unwrap_err().contains("needs a value"));
+        assert!(parse(&["--wrapper-nmae=x"]).unwrap_err().contains("unknown flag --wrapper-nmae"));
+        assert_eq!(parse(&[]).unwrap(), (WrapperArgs::default(), vec![]));
+    }
+}
@@ -0,0 +1,199 @@
+use crate::registry::Entry;
+use crate::state::{self, Status};
+use serde_json::json;
+use std::path::Path;
+
+/// Output format of `cursor-cli-wrapper-backend bar`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// Waybar `custom` module JSON (`return-type = "json"`).
+    Waybar,
+    /// A block of the i3bar protocol (i3blocks, i3status-rust, ...).
+    I3bar,
+    /// Just the text.
+    Plain,
+}
+
+impl Format {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "waybar" => Some(Format::Waybar),
+            "i3bar" => Some(Format::I3bar),
+            "plain" => Some(Format::Plain),
+            _ => None,
+        }
+    }
+}
+
+/// Category a status is counted under; also the CSS class.
+fn category(status: Status) -> &'static str {
+    match status {
+        Status::Idle => "idle",
+        Status::InProgress | Status::Thinking | Status::Generating | Status::Tool => "busy",
+        Status::Waiting => "waiting",
+        Status::Stalled => "stalled",
+        Status::Error => "error",
+    }
+}
+
+/// Categories in the order they are listed, most urgent first.
+const CATEGORIES: [&str; 5] = ["error", "stalled", "waiting", "busy", "idle"];
+
+/// All running sessions, summarised for a status bar.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Summary {
+    /// e.g. "2 busy · 1 waiting"; empty when no session is running.
+    pub text: String,
+    /// One line per session: "repo/branch: STATUS".
+    pub tooltip: String,
+    /// Category of the most urgent status, or "none".
+    pub class: &'static str,
+}
+
+impl Summary {
+    pub fn new(entries: &[Entry]) -> Self {
+        let statuses: Vec<Status> = entries.iter().filter_map(|e| Status::parse(&e.status)).collect();
+
+        let text = CATEGORIES
+            .iter()
+            .filter_map(|&cat| {
+                let count = statuses.iter().filter(|&&s| category(s) == cat).count();
+                (count > 0).then(|| format!("{count} {cat}"))
+            })
+            .collect::<Vec<_>>()
+            .join(" · ");
+
+        let tooltip = entries
+            .iter()
+            .map(|e| {
+                let place = match (e.repo.as_str(), e.branch.as_str()) {
+                    ("", _) => e.cwd.clone(),
+                    (repo, "") => repo.to_string(),
+                    (repo, branch) => format!("{repo}/{branch}"),
+                };
+                format!("{place}: {}", e.status_text)
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let class = state::aggregate_status(statuses).map_or("none", category);
+        Self {
+            text,
+            tooltip,
+            class,
+        }
+    }
+
+    /// One line of output in `format`.
+    pub fn render(&self, format: Format) -> String {
+        match format {
+            Format::Waybar => json!({
+                "text": self.text,
+                "tooltip": self.tooltip,
+                "class": self.class,
+                "alt": self.class,
+            })
+            .to_string(),
+            Format::I3bar => self.i3bar_block().to_string(),
+            Format::Plain => self.text.clone(),
+        }
+    }
+
+    pub fn i3bar_block(&self) -> serde_json::Value {
+        json!({
+            "name": "cursor-cli-wrapper",
+            "full_text": self.text,
+            "urgent": matches!(self.class, "error" | "stalled"),
+        })
+    }
+}
+
+/// Top-level directory of the git work tree containing `dir`, found
+/// without running git.
+pub fn repo_root(dir: &Path) -> Option<&Path> {
+    dir.ancestors().find(|d| d.join(".git").exists())
+}
+
+/// Compact shell-prompt indicator for the sessions running inside the git
+/// work tree containing `dir` (e.g. `cursor:WAITING,THINKING`, most urgent
+/// first), or `None` if there are none.
+pub fn prompt_segment(entries: &[Entry], dir: &Path) -> Option<String> {
+    let root = repo_root(dir)?;
+    let mut here: Vec<(Status, &str)> = entries
+        .iter()
+        .filter(|e| Path::new(&e.cwd).starts_with(root))
+        .filter_map(|e| Some((Status::parse(&e.status)?, e.status_text.as_str())))
+        .collect();
+    if here.is_empty() {
+        return None;
+    }
+    here.sort_by_key(|(status, _)| std::cmp::Reverse(status.urgency()));
+    let texts: Vec<&str> = here.iter().map(|(_, text)| *text).collect();
+    Some(format!("cursor:{}", texts.join(",")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(status: &str, repo: &str, branch: &str) -> Entry {
+        Entry {
+            status: status.to_string(),
+            status_text: status.to_string(),
+            repo: repo.to_string(),
+            branch: branch.to_string(),
+            cwd: "/tmp/x".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn summary_counts_by_category() {
+        let summary = Summary::new(&[
+            entry("THINKING", "app", "main"),
+            entry("WAITING", "lib", ""),
+            entry("INPROGRESS", "", ""),
+        ]);
+        assert_eq!(summary.text, "1 waiting · 2 busy");
+        assert_eq!(summary.tooltip, "app/main: THINKING\nlib: WAITING\n/tmp/x: INPROGRESS");
+        assert_eq!(summary.class, "waiting");
+        assert_eq!(summary.render(Format::Plain), "1 waiting · 2 busy");
+    }
+
+    #[test]
+    fn empty_summary() {
+        let summary = Summary::new(&[]);
+        assert_eq!(summary.text, "");
+        assert_eq!(summary.class, "none");
+        let waybar: serde_json::Value = serde_json::from_str(&summary.render(Format::Waybar)).unwrap();
+        assert_eq!(waybar["class"], "none");
+    }
+
+    #[test]
+    fn errors_are_urgent_in_i3bar() {
+        let block = Summary::new(&[entry("ERROR", "app", "main")]).i3bar_block();
+        assert_eq!(block["full_text"], "1 error");
+        assert_eq!(block["urgent"], true);
+    }
+
+    #[test]
+    fn prompt_segment_matches_the_work_tree() {
+        let root = std::env::temp_dir().join(format!("ccw-{}-segment", std::process::id()));
+        std::fs::create_dir_all(root.join(".git")).unwrap();
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        let at = |status: &str, cwd: &Path| Entry {
+            cwd: cwd.display().to_string(),
+            ..entry(status, "", "")
+        };
+        let entries = [
+            at("THINKING", &root),
+            at("WAITING", &root.join("src")),
+            at("ERROR", Path::new("/elsewhere")),
+        ];
+
+        let segment = prompt_segment(&entries, &root.join("src"));
+        assert_eq!(segment.as_deref(), Some("cursor:WAITING,THINKING"));
+        assert_eq!(prompt_segment(&entries[2..], &root), None);
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
@@ -1,15 +1,27 @@
-use cursor_cli_wrapper::{config, state};
+use cursor_cli_wrapper::state::Status;
+use cursor_cli_wrapper::{bar, config, mux, placeholders, registry, state, term, tmux};
+use std::io::Write;
 
 fn print_usage() {
+    let statuses: Vec<&str> = Status::ALL.iter().map(|s| s.as_str()).collect();
     eprintln!("Usage: cursor-cli-wrapper-backend <command>");
     eprintln!();
     eprintln!("Commands:");
     eprintln!("  --notify            Send a test notification");
-    eprintln!("  --status <value>    Set tmux status (INPROGRESS, WAITING, or empty to clear)");
+    eprintln!("  --status <value>    Set tmux status (empty to clear)");
+    eprintln!("                      One of: {}", statuses.join(", "));
+    eprintln!("  --check-config      Validate the config file and its templates");
+    eprintln!("  --prune             Remove status files of wrappers that are gone");
+    eprintln!("  bar [--format waybar|i3bar|plain] [--follow]");
+    eprintln!("                      Summarise running sessions for a status bar");
+    eprintln!("  prompt-segment      Show sessions running in the current git repo");
+    eprintln!("  list [--json] [--name <name>] [--repo <repo>]");
+    eprintln!("                      List running sessions");
 }
 
 fn cmd_notify() {
     let cfg = config::Config::load();
+    placeholders::refresh_blocking();
     let args = cfg.general.notify_send_args();
 
     let status = std::process::Command::new("notify-send")
@@ -23,8 +35,168 @@ fn cmd_notify() {
 }
 
 fn cmd_status(value: &str) {
+    let status = if value.is_empty() {
+        None
+    } else if let Some(status) = Status::parse(value) {
+        Some(status)
+    } else {
+        eprintln!("Unknown status: {value}");
+        print_usage();
+        std::process::exit(1);
+    };
     let cfg = config::Config::load();
-    state::set_tmux_status(value, cfg.hooks.status_change.as_deref());
+    placeholders::refresh_blocking();
+    state::set_status(status, &cfg);
+
+    // set_status only queues its work: tmux and zellij updates run on
+    // background threads, and the title and user vars wait for a writer.
+    // Finish all of it before returning, or exiting drops it.
+    tmux::flush();
+    mux::flush();
+    let mut stdout = std::io::stdout();
+    let _ = stdout.write_all(&term::take_pending());
+    let _ = stdout.flush();
+}
+
+fn cmd_check_config() {
+    let Some(path) = config::Config::config_path() else {
+        eprintln!("Could not determine the config directory");
+        std::process::exit(1);
+    };
+    let contents = match std::fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(e) => {
+            eprintln!("{}: {e}", path.display());
+            std::process::exit(1);
+        }
+    };
+    let cfg = match config::Config::parse(&contents) {
+        Ok(cfg) => cfg,
+        Err(e) => {
+            eprintln!("{}: {e}", path.display());
+            std::process::exit(1);
+        }
+    };
+
+    let problems = cfg.check();
+    if problems.is_empty() {
+        println!("{}: OK", path.display());
+        return;
+    }
+    for problem in &problems {
+        eprintln!("{}: {problem}", path.display());
+    }
+    std::process::exit(1);
+}
+
+fn cmd_prune() {
+    let running = registry::entries();
+    println!("{}: {} running", registry::dir().display(), running.len());
+}
+
+fn cmd_bar(args: &[String]) {
+    let mut format = bar::Format::Plain;
+    let mut follow = false;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--format" => {
+                let name = args.next().map(|s| s.as_str()).unwrap_or("");
+                format = bar::Format::parse(name).unwrap_or_else(|| {
+                    eprintln!("Unknown format: {name}");
+                    print_usage();
+                    std::process::exit(1);
+                });
+            }
+            "--follow" => follow = true,
+            _ => {
+                eprintln!("Unknown option: {arg}");
+                print_usage();
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if !follow {
+        println!("{}", bar::Summary::new(&registry::entries()).render(format));
+        return;
+    }
+
+    // Status files change at most a few times a second; polling also
+    // notices wrappers that died without removing theirs.
+    let mut stdout = std::io::stdout();
+    if format == bar::Format::I3bar {
+        let _ = writeln!(stdout, "{{\"version\":1}}\n[");
+    }
+    let mut last = None;
+    loop {
+        let summary = bar::Summary::new(&registry::entries());
+        if last.as_ref() != Some(&summary) {
+            let line = match format {
+                bar::Format::I3bar => {
+                    let sep = if last.is_some() { "," } else { "" };
+                    format!("{sep}[{}]", summary.i3bar_block())
+                }
+                _ => summary.render(format),
+            };
+            if writeln!(stdout, "{line}").and_then(|_| stdout.flush()).is_err() {
+                return;
+            }
+            last = Some(summary);
+        }
+        std::thread::sleep(std::time::Duration::from_millis(500));
+    }
+}
+
+/// Runs on every shell prompt, so it only reads the status files: no
+/// config, tmux or git.
+fn cmd_prompt_segment() {
+    let dir = std::env::current_dir().unwrap_or_default();
+    match bar::prompt_segment(&registry::entries(), &dir) {
+        Some(segment) => println!("{segment}"),
+        None => std::process::exit(1),
+    }
+}
+
+fn cmd_list(args: &[String]) {
+    let mut json = false;
+    let mut name = None;
+    let mut repo = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next().unwrap_or_else(|| {
+                eprintln!("Missing value for {arg}");
+                print_usage();
+                std::process::exit(1);
+            })
+        };
+        match arg.as_str() {
+            "--json" => json = true,
+            "--name" => name = Some(value()),
+            "--repo" => repo = Some(value()),
+            _ => {
+                eprintln!("Unknown option: {arg}");
+                print_usage();
+                std::process::exit(1);
+            }
+        }
+    }
+
+    let entries: Vec<registry::Entry> = registry::entries()
+        .into_iter()
+        .filter(|e| name.is_none_or(|n| e.name == *n))
+        .filter(|e| repo.is_none_or(|r| e.repo == *r))
+        .collect();
+    if json {
+        println!("{}", serde_json::to_string_pretty(&entries).unwrap_or_default());
+    } else {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        print!("{}", registry::table(&entries, now));
+    }
 }
 
 fn main() {
@@ -36,6 +208,11 @@ fn main() {
             let value = args.get(1).map(|s| s.as_str()).unwrap_or("");
             cmd_status(value);
         }
+        Some("--check-config") => cmd_check_config(),
+        Some("--prune") => cmd_prune(),
+        Some("bar") => cmd_bar(&args[1..]),
+        Some("prompt-segment") => cmd_prompt_segment(),
+        Some("list") => cmd_list(&args[1..]),
         _ => {
             print_usage();
             std::process::exit(1);
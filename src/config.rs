@@ -1,5 +1,11 @@
+use crate::state::{self, Status};
+use crate::{agent, log, monitor, placeholders, template};
+use inotify::{Inotify, WatchMask};
 use serde::Deserialize;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock, RwLock};
+use tokio::signal::unix::{signal, Signal, SignalKind};
+use tokio::sync::mpsc;
 
 fn default_notification_title() -> String {
     "Cursor Agent".to_string()
@@ -13,6 +19,38 @@ fn default_notification_urgency() -> Urgency {
     Urgency::Normal
 }
 
+fn default_error_title() -> String {
+    "Cursor Agent: error".to_string()
+}
+
+fn default_error_body() -> String {
+    "{error}".to_string()
+}
+
+fn default_retry_backoff() -> u64 {
+    30
+}
+
+fn default_stall_timeout() -> u64 {
+    30 * 60
+}
+
+fn default_stall_title() -> String {
+    "Cursor Agent: stalled".to_string()
+}
+
+fn default_stall_body() -> String {
+    "No progress, {duration} into the turn - {git_branch}".to_string()
+}
+
+fn default_context_warning_title() -> String {
+    "Cursor Agent: context filling up".to_string()
+}
+
+fn default_context_warning_body() -> String {
+    "Context window at {context} - consider compacting".to_string()
+}
+
 #[derive(Debug, Clone, Copy, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum Urgency {
@@ -31,26 +69,60 @@ impl Urgency {
     }
 }
 
-#[derive(Debug, Clone, Deserialize)]
+/// Environment variable naming the `[profile.<name>]` section to apply.
+pub const PROFILE_ENV: &str = "CURSOR_WRAPPER_PROFILE";
+
+/// Profile chosen at launch with `--wrapper-profile`, taking precedence
+/// over [`PROFILE_ENV`].
+static SELECTED_PROFILE: OnceLock<String> = OnceLock::new();
+
+/// Select the profile to apply on every (re)load. Only the first call has
+/// an effect.
+pub fn select_profile(name: &str) {
+    let _ = SELECTED_PROFILE.set(name.to_string());
+}
+
+/// The profile requested at launch, if any. Whether it exists is only
+/// known once the config is parsed, see [`Config::profile`].
+fn selected_profile() -> Option<String> {
+    SELECTED_PROFILE
+        .get()
+        .cloned()
+        .or_else(|| std::env::var(PROFILE_ENV).ok())
+        .filter(|name| !name.is_empty())
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
 pub struct Config {
     #[serde(default)]
     pub general: General,
 
     #[serde(default)]
     pub hooks: Hooks,
-}
 
-impl Default for Config {
-    fn default() -> Self {
-        Self {
-            general: General::default(),
-            hooks: Hooks::default(),
-        }
-    }
+    #[serde(default)]
+    pub status: StatusStyles,
+
+    #[serde(default)]
+    pub errors: Errors,
+
+    #[serde(default)]
+    pub watchdog: Watchdog,
+
+    /// `[[detection]]` profiles, tried before the built-in ones.
+    #[serde(default)]
+    pub detection: Vec<DetectionProfile>,
+
+    /// Name of the `[profile.<name>]` section that was applied, if any.
+    #[serde(skip)]
+    pub profile: Option<String>,
 }
 
 #[derive(Debug, Clone, Default, Deserialize)]
 pub struct Hooks {
+    /// Command to run on every status change. The placeholder `{status}`
+    /// is the canonical name of the new status (e.g. `WAITING`), and
+    /// `{status_text}` its display text from `[status]`.
     #[serde(default, rename = "status-change")]
     pub status_change: Option<String>,
 
@@ -63,6 +135,197 @@ pub struct Hooks {
     /// (`normal` or `insert`).
     #[serde(default, rename = "vim-mode-change")]
     pub vim_mode_change: Option<String>,
+
+    /// Command to run when the agent mode changes (e.g. ask -> agent).
+    /// The placeholder `{agent_mode}` is replaced with the new mode name
+    /// (`ask`, `agent` or `plan`).
+    #[serde(default, rename = "agent-mode-change")]
+    pub agent_mode_change: Option<String>,
+
+    /// Command to run when an error screen ends a turn. The placeholder
+    /// `{error}` holds the matching line.
+    #[serde(default)]
+    pub error: Option<String>,
+
+    /// Command to run when the watchdog finds the agent stalled.
+    #[serde(default)]
+    pub stalled: Option<String>,
+
+    /// Command to run when context-window usage crosses
+    /// `context-warning-threshold`.
+    #[serde(default, rename = "context-warning")]
+    pub context_warning: Option<String>,
+}
+
+/// How a status is displayed: `[status.<name>]` with `text`, `icon` and
+/// `colour` keys, or `<name> = "text"` as a shorthand.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(from = "StatusStyleRepr")]
+pub struct StatusStyle {
+    /// Value of `@ai-agent-status` and `{status}`; defaults to the
+    /// canonical name (e.g. `WAITING`).
+    pub text: Option<String>,
+    /// Value of `@ai-agent-status-icon`.
+    pub icon: Option<String>,
+    /// Value of `@ai-agent-status-colour`.
+    pub colour: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StatusStyleRepr {
+    Text(String),
+    Full {
+        #[serde(default)]
+        text: Option<String>,
+        #[serde(default)]
+        icon: Option<String>,
+        #[serde(default, alias = "color")]
+        colour: Option<String>,
+    },
+}
+
+impl From<StatusStyleRepr> for StatusStyle {
+    fn from(repr: StatusStyleRepr) -> Self {
+        match repr {
+            StatusStyleRepr::Text(text) => Self {
+                text: Some(text),
+                ..Self::default()
+            },
+            StatusStyleRepr::Full { text, icon, colour } => Self { text, icon, colour },
+        }
+    }
+}
+
+/// The `[status]` section: display style for each [`Status`].
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct StatusStyles {
+    #[serde(default)]
+    pub idle: StatusStyle,
+    #[serde(default)]
+    pub inprogress: StatusStyle,
+    #[serde(default)]
+    pub waiting: StatusStyle,
+    #[serde(default)]
+    pub error: StatusStyle,
+    #[serde(default)]
+    pub stalled: StatusStyle,
+    #[serde(default)]
+    pub thinking: StatusStyle,
+    #[serde(default)]
+    pub generating: StatusStyle,
+    #[serde(default)]
+    pub tool: StatusStyle,
+}
+
+impl StatusStyles {
+    pub fn style(&self, status: Status) -> StatusStyle {
+        match status {
+            Status::Idle => &self.idle,
+            Status::InProgress => &self.inprogress,
+            Status::Waiting => &self.waiting,
+            Status::Error => &self.error,
+            Status::Stalled => &self.stalled,
+            Status::Thinking => &self.thinking,
+            Status::Generating => &self.generating,
+            Status::Tool => &self.tool,
+        }
+        .clone()
+    }
+
+    /// Display text for `status`.
+    pub fn text(&self, status: Status) -> String {
+        self.style(status)
+            .text
+            .unwrap_or_else(|| status.as_str().to_string())
+    }
+}
+
+/// Detection of error screens (network failures, rate limits, expired
+/// auth) that end a turn without the agent finishing its work.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Errors {
+    /// Regexes matched against each line of output while the agent is busy.
+    /// None by default: the answer itself is output too, and one that
+    /// explains an HTTP 401 must not end the turn as an error.
+    #[serde(default)]
+    pub patterns: Vec<String>,
+
+    #[serde(default = "default_error_title", rename = "notification-title")]
+    pub notification_title: String,
+
+    #[serde(default = "default_error_body", rename = "notification-body")]
+    pub notification_body: String,
+
+    /// How many times to re-send the last prompt after an error. 0 disables.
+    #[serde(default, rename = "retry-attempts")]
+    pub retry_attempts: u32,
+
+    /// Seconds to wait before re-sending the prompt.
+    #[serde(default = "default_retry_backoff", rename = "retry-backoff")]
+    pub retry_backoff: u64,
+}
+
+impl Default for Errors {
+    fn default() -> Self {
+        Self {
+            patterns: Vec::new(),
+            notification_title: default_error_title(),
+            notification_body: default_error_body(),
+            retry_attempts: 0,
+            retry_backoff: default_retry_backoff(),
+        }
+    }
+}
+
+/// How to recognise a busy agent in the output of some agent versions.
+#[derive(Debug, Clone, Deserialize)]
+pub struct DetectionProfile {
+    pub name: String,
+
+    /// Versions this profile applies to, as `<from>..<to>` (see
+    /// [`agent::VersionRange`]). Every version when unset.
+    #[serde(default)]
+    pub versions: Option<String>,
+
+    /// Regex matching the busy spinner in ANSI-stripped output.
+    pub spinner: String,
+}
+
+/// Watchdog raising STALLED when a busy agent shows no progress.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Watchdog {
+    /// Seconds without new output (besides the spinner) before the agent is
+    /// considered stalled. 0 disables the watchdog.
+    #[serde(default = "default_stall_timeout", rename = "stall-timeout")]
+    pub stall_timeout: u64,
+
+    /// Send Ctrl+C to the agent when it stalls.
+    #[serde(default)]
+    pub interrupt: bool,
+
+    #[serde(default = "default_stall_title", rename = "notification-title")]
+    pub notification_title: String,
+
+    #[serde(default = "default_stall_body", rename = "notification-body")]
+    pub notification_body: String,
+}
+
+impl Default for Watchdog {
+    fn default() -> Self {
+        Self {
+            stall_timeout: default_stall_timeout(),
+            interrupt: false,
+            notification_title: default_stall_title(),
+            notification_body: default_stall_body(),
+        }
+    }
+}
+
+impl Watchdog {
+    pub fn timeout(&self) -> Option<std::time::Duration> {
+        (self.stall_timeout > 0).then(|| std::time::Duration::from_secs(self.stall_timeout))
+    }
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -81,6 +344,28 @@ pub struct General {
 
     #[serde(default, rename = "notification-icon")]
     pub notification_icon: Option<String>,
+
+    /// Context-window usage (in percent) at which to warn. Disabled when unset.
+    #[serde(default, rename = "context-warning-threshold")]
+    pub context_warning_threshold: Option<f32>,
+
+    #[serde(default = "default_context_warning_title", rename = "context-warning-title")]
+    pub context_warning_title: String,
+
+    #[serde(default = "default_context_warning_body", rename = "context-warning-body")]
+    pub context_warning_body: String,
+
+    #[serde(default = "default_notification_urgency", rename = "context-warning-urgency")]
+    pub context_warning_urgency: Urgency,
+
+    /// Terminal window title (OSC 2), set on every status change. Empty
+    /// (the default) leaves the title alone.
+    #[serde(default, rename = "window-title")]
+    pub window_title: String,
+
+    /// Path of the cursor-agent binary, tried after `$CURSOR_AGENT_PATH`.
+    #[serde(default, rename = "agent-path")]
+    pub agent_path: Option<String>,
 }
 
 impl Default for General {
@@ -91,6 +376,12 @@ impl Default for General {
             notification_urgency: default_notification_urgency(),
             notification_app_name: None,
             notification_icon: None,
+            context_warning_threshold: None,
+            context_warning_title: default_context_warning_title(),
+            context_warning_body: default_context_warning_body(),
+            context_warning_urgency: default_notification_urgency(),
+            window_title: String::new(),
+            agent_path: None,
         }
     }
 }
@@ -99,10 +390,17 @@ impl General {
     /// Build the full `notify-send` argument list from the config,
     /// resolving placeholders in title and body.
     pub fn notify_send_args(&self) -> Vec<String> {
-        let mut args = vec![
-            "-u".to_string(),
-            self.notification_urgency.as_str().to_string(),
-        ];
+        self.notify_send_args_for(
+            &self.notification_title,
+            &self.notification_body,
+            self.notification_urgency,
+        )
+    }
+
+    /// Build a `notify-send` argument list for the given title and body
+    /// templates, sharing the configured app name and icon.
+    pub fn notify_send_args_for(&self, title: &str, body: &str, urgency: Urgency) -> Vec<String> {
+        let mut args = vec!["-u".to_string(), urgency.as_str().to_string()];
         if let Some(ref app_name) = self.notification_app_name {
             args.push("--app-name".to_string());
             args.push(app_name.clone());
@@ -111,8 +409,8 @@ impl General {
             args.push("--icon".to_string());
             args.push(icon.clone());
         }
-        args.push(resolve_placeholders(&self.notification_title));
-        args.push(resolve_placeholders(&self.notification_body));
+        args.push(resolve_placeholders(title));
+        args.push(resolve_placeholders(body));
         args
     }
 }
@@ -124,105 +422,391 @@ impl Config {
     pub fn load() -> Self {
         Self::config_path()
             .and_then(|path| std::fs::read_to_string(&path).ok())
-            .and_then(|contents| toml::from_str::<Config>(&contents).ok())
+            .and_then(|contents| Self::parse(&contents).ok())
             .unwrap_or_default()
     }
 
-    pub(crate) fn config_path() -> Option<PathBuf> {
+    /// Parse config file contents, applying the [`selected_profile`].
+    ///
+    /// A profile is a `[profile.<name>]` table whose `general` and `hooks`
+    /// sub-tables override individual keys of the top-level sections.
+    /// An unknown profile name is logged and otherwise ignored.
+    pub fn parse(contents: &str) -> Result<Self, toml::de::Error> {
+        Self::parse_with_profile(contents, selected_profile())
+    }
+
+    fn parse_with_profile(contents: &str, profile: Option<String>) -> Result<Self, toml::de::Error> {
+        let mut table = toml::from_str::<toml::Table>(contents)?;
+        let profiles = table.remove("profile");
+
+        let mut applied = None;
+        if let Some(name) = profile {
+            match profiles.as_ref().and_then(|p| p.get(&name)) {
+                Some(toml::Value::Table(profile)) => {
+                    for (section, overrides) in profile {
+                        let toml::Value::Table(overrides) = overrides else {
+                            continue;
+                        };
+                        let base = table
+                            .entry(section.clone())
+                            .or_insert_with(|| toml::Value::Table(toml::Table::new()));
+                        if let toml::Value::Table(base) = base {
+                            base.extend(overrides.clone());
+                        }
+                    }
+                    applied = Some(name);
+                }
+                _ => log::info(format!("config: ignoring unknown profile '{name}'")),
+            }
+        }
+
+        let mut cfg: Config = table.try_into()?;
+        cfg.profile = applied;
+        Ok(cfg)
+    }
+
+    /// Validate every template in the config, returning one message per
+    /// syntax error or unknown placeholder.
+    pub fn check(&self) -> Vec<String> {
+        let g = &self.general;
+        let h = &self.hooks;
+        let fields: Vec<(&str, Option<&str>, &[&str])> = vec![
+            ("general.notification-title", Some(&g.notification_title), &[]),
+            ("general.notification-body", Some(&g.notification_body), &[]),
+            ("general.context-warning-title", Some(&g.context_warning_title), &[]),
+            ("general.context-warning-body", Some(&g.context_warning_body), &[]),
+            ("general.window-title", Some(&g.window_title), &[]),
+            ("errors.notification-title", Some(&self.errors.notification_title), &[]),
+            ("errors.notification-body", Some(&self.errors.notification_body), &[]),
+            ("watchdog.notification-title", Some(&self.watchdog.notification_title), &[]),
+            ("watchdog.notification-body", Some(&self.watchdog.notification_body), &[]),
+            ("hooks.status-change", h.status_change.as_deref(), &["status_text"]),
+            ("hooks.esc-in-normal", h.esc_in_normal.as_deref(), &[]),
+            ("hooks.vim-mode-change", h.vim_mode_change.as_deref(), &["vim_mode"]),
+            ("hooks.agent-mode-change", h.agent_mode_change.as_deref(), &[]),
+            ("hooks.context-warning", h.context_warning.as_deref(), &[]),
+            ("hooks.error", h.error.as_deref(), &[]),
+            ("hooks.stalled", h.stalled.as_deref(), &[]),
+        ];
+
+        let mut problems = Vec::new();
+        if let Some(name) = selected_profile()
+            && self.profile.is_none()
+        {
+            problems.push(format!("{PROFILE_ENV}: profile '{name}' not found"));
+        }
+        for (field, value, extra) in fields {
+            let Some(value) = value else { continue };
+            match template::Template::parse(value) {
+                Ok(t) => {
+                    for name in t.placeholders() {
+                        if !PLACEHOLDERS.contains(&name) && !extra.contains(&name) {
+                            problems.push(format!("{field}: unknown placeholder '{{{name}}}'"));
+                        }
+                    }
+                    for offset in t.unmatched_braces() {
+                        problems.push(format!(
+                            "{field}: '}}}}' at byte {offset} closes no '{{{{' and is kept as two \
+                             braces; write '}}' if one was meant"
+                        ));
+                    }
+                }
+                Err(e) => problems.push(format!("{field}: {e}")),
+            }
+        }
+        for pattern in &self.errors.patterns {
+            if let Err(e) = regex::Regex::new(pattern) {
+                problems.push(format!("errors.patterns: invalid regex {pattern:?}: {e}"));
+            }
+        }
+        for profile in &self.detection {
+            let name = &profile.name;
+            if let Err(e) = monitor::Detection::new(name, &profile.spinner) {
+                problems.push(format!("detection '{name}': invalid spinner regex: {e}"));
+            }
+            if let Some(Err(e)) = profile.versions.as_deref().map(agent::VersionRange::parse) {
+                problems.push(format!("detection '{name}': {e}"));
+            }
+        }
+        problems
+    }
+
+    pub fn config_path() -> Option<PathBuf> {
         dirs::config_dir().map(|d| d.join("cursor-cli-wrapper").join("config.toml"))
     }
 }
 
 /// Watch the config file for changes and reload when valid.
 ///
-/// Polls the file's modification time every 2 seconds. If the file changes
-/// and the new contents parse successfully, the shared config is updated.
-/// Invalid configs are silently ignored (the previous config is kept).
-pub async fn watch_config(shared: std::sync::Arc<std::sync::RwLock<Config>>) {
+/// Uses inotify on the config *directory* so that editors which save by
+/// writing a temporary file and renaming it over `config.toml` are picked up
+/// as well as in-place writes. Sending `SIGHUP` to the wrapper forces a
+/// reload. Every reload attempt is written to the log; invalid configs are
+/// ignored and the previous config is kept.
+///
+/// If the config directory does not exist at startup only `SIGHUP` reloads
+/// are available.
+pub async fn watch_config(shared: Arc<RwLock<Config>>) {
     let Some(path) = Config::config_path() else {
         return;
     };
 
-    let mut last_modified = std::fs::metadata(&path)
-        .and_then(|m| m.modified())
-        .ok();
-
-    let mut interval = tokio::time::interval(std::time::Duration::from_secs(2));
-    interval.tick().await; // first tick is immediate, skip it
+    let mut file_events = spawn_dir_watcher(&path);
+    let mut hangup = signal(SignalKind::hangup()).ok();
 
     loop {
-        interval.tick().await;
+        let reason = tokio::select! {
+            Some(()) = recv_opt(&mut file_events) => "file changed",
+            Some(()) = recv_hangup(&mut hangup) => "SIGHUP",
+            else => return,
+        };
+        reload(&shared, &path, reason);
+    }
+}
 
-        let current_modified = std::fs::metadata(&path)
-            .and_then(|m| m.modified())
-            .ok();
+/// Re-read the config file into `shared`, logging the outcome.
+fn reload(shared: &RwLock<Config>, path: &Path, reason: &str) {
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) => {
+            log::info(format!("config reload ({reason}): cannot read {}: {e}", path.display()));
+            return;
+        }
+    };
+    match Config::parse(&contents) {
+        Ok(new_cfg) => {
+            state::set_profile(new_cfg.profile.clone());
+            if let Ok(mut cfg) = shared.write() {
+                *cfg = new_cfg;
+                log::info(format!("config reloaded ({reason}) from {}", path.display()));
+            }
+        }
+        Err(e) => {
+            log::info(format!("config reload ({reason}): keeping previous config: {e}"));
+        }
+    }
+}
 
-        if current_modified != last_modified {
-            last_modified = current_modified;
+/// Start a background thread that watches the directory containing `path`
+/// and sends a message whenever `path` is written or renamed into place.
+///
+/// Returns `None` if inotify is unavailable or the directory cannot be
+/// watched.
+fn spawn_dir_watcher(path: &Path) -> Option<mpsc::UnboundedReceiver<()>> {
+    let dir = path.parent()?;
+    let file_name = path.file_name()?.to_os_string();
+
+    let mut inotify = Inotify::init().ok()?;
+    if let Err(e) = inotify
+        .watches()
+        .add(dir, WatchMask::CLOSE_WRITE | WatchMask::MOVED_TO)
+    {
+        log::info(format!("config watch: cannot watch {}: {e}", dir.display()));
+        return None;
+    }
 
-            if let Ok(contents) = std::fs::read_to_string(&path) {
-                if let Ok(new_cfg) = toml::from_str::<Config>(&contents) {
-                    if let Ok(mut cfg) = shared.write() {
-                        *cfg = new_cfg;
-                    }
-                }
+    let (tx, rx) = mpsc::unbounded_channel();
+    std::thread::spawn(move || {
+        let mut buf = [0u8; 4096];
+        loop {
+            let Ok(events) = inotify.read_events_blocking(&mut buf) else {
+                return;
+            };
+            let touched = events
+                .filter_map(|ev| ev.name)
+                .any(|name| name == file_name);
+            if touched && tx.send(()).is_err() {
+                return;
             }
         }
+    });
+    Some(rx)
+}
+
+async fn recv_opt(rx: &mut Option<mpsc::UnboundedReceiver<()>>) -> Option<()> {
+    match rx {
+        Some(rx) => rx.recv().await,
+        None => None,
+    }
+}
+
+async fn recv_hangup(sig: &mut Option<Signal>) -> Option<()> {
+    match sig {
+        Some(sig) => sig.recv().await,
+        None => None,
     }
 }
 
-/// Replace `{cwd}`, `{git_branch}`, `{git_repo}`, and `{tmux-session}`
-/// placeholders in the given template string with their current values.
+/// Placeholders available in every template.
+pub const PLACEHOLDERS: &[&str] = &[
+    "cwd",
+    "git_branch",
+    "git_repo",
+    "git_commit",
+    "git_dirty",
+    "git_diffstat",
+    "hostname",
+    "tmux-session",
+    "tmux-window",
+    "tmux-pane",
+    "mux-session",
+    "profile",
+    "session",
+    "status",
+    "duration",
+    "turn",
+    "model",
+    "context",
+    "agent_mode",
+    "phase",
+    "substatus",
+    "tokens",
+    "error",
+];
+
+/// Render a notification or hook template, resolving the
+/// [`PLACEHOLDERS`] with their current values.
 ///
 /// Placeholders that cannot be resolved (e.g. not in a git repo) are
 /// replaced with an empty string.
 pub fn resolve_placeholders(template: &str) -> String {
-    let mut result = template.to_string();
-
-    if result.contains("{cwd}") {
-        let cwd = std::env::current_dir()
-            .map(|p| p.display().to_string())
-            .unwrap_or_default();
-        result = result.replace("{cwd}", &cwd);
-    }
-
-    if result.contains("{git_branch}") {
-        let branch = std::process::Command::new("git")
-            .args(["rev-parse", "--abbrev-ref", "HEAD"])
-            .output()
-            .ok()
-            .filter(|o| o.status.success())
-            .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string())
-            .unwrap_or_default();
-        result = result.replace("{git_branch}", &branch);
-    }
-
-    if result.contains("{git_repo}") {
-        let repo = std::process::Command::new("git")
-            .args(["rev-parse", "--show-toplevel"])
-            .output()
-            .ok()
-            .filter(|o| o.status.success())
-            .and_then(|o| {
-                let path = String::from_utf8_lossy(&o.stdout).trim().to_string();
-                std::path::Path::new(&path)
-                    .file_name()
-                    .map(|n| n.to_string_lossy().into_owned())
-            })
-            .unwrap_or_default();
-        result = result.replace("{git_repo}", &repo);
-    }
-
-    if result.contains("{tmux-session}") {
-        let session = std::process::Command::new("tmux")
-            .args(["display-message", "-p", "#S"])
-            .output()
-            .ok()
-            .filter(|o| o.status.success())
-            .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string())
-            .unwrap_or_default();
-        result = result.replace("{tmux-session}", &session);
-    }
-
-    result
+    resolve_placeholders_with(template, &[])
+}
+
+/// Like [`resolve_placeholders`], with additional context-specific values
+/// such as `{status}` or `{vim_mode}` for hooks.
+pub fn resolve_placeholders_with(template: &str, extra: &[(&str, &str)]) -> String {
+    template::render(template, &mut |name| lookup_with(name, extra))
+}
+
+/// Render a hook command for `sh -c`: like [`resolve_placeholders_with`],
+/// with every value shell-escaped (see [`template::shell_escape`]) so
+/// branch names, model names or error lines can't inject commands.
+pub fn resolve_hook(template: &str, extra: &[(&str, &str)]) -> String {
+    template::render_shell(template, &mut |name| lookup_with(name, extra))
+}
+
+fn lookup_with(name: &str, extra: &[(&str, &str)]) -> Option<String> {
+    extra
+        .iter()
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value.to_string())
+        .or_else(|| lookup_placeholder(name))
+}
+
+/// Look up the current value of one of the [`PLACEHOLDERS`], or `None` if
+/// `name` is not a known placeholder. Values come from the
+/// [`placeholders`] cache as of the last refresh; this never spawns `git`
+/// or `tmux`.
+fn lookup_placeholder(name: &str) -> Option<String> {
+    let session = || state::session();
+    let value = match name {
+        "profile" => session().profile.unwrap_or_default(),
+        "session" => session().name,
+        "status" => session().status_text,
+        "duration" => session()
+            .turn_duration()
+            .map(state::format_duration)
+            .unwrap_or_default(),
+        "turn" => session().turn.to_string(),
+        "model" => session().model,
+        "context" => session()
+            .context
+            .map(|pct| format!("{pct}%"))
+            .unwrap_or_default(),
+        "phase" => session().phase.unwrap_or_default(),
+        "substatus" => session()
+            .phase
+            .map(|p| Status::from_phase(&p).as_str().to_lowercase())
+            .unwrap_or_default(),
+        "tokens" => session()
+            .tokens
+            .map(state::format_count)
+            .unwrap_or_default(),
+        "error" => session().error.unwrap_or_default(),
+        "agent_mode" => state::get_agent_mode()
+            .map(|m| m.as_str().to_string())
+            .unwrap_or_default(),
+        _ if PLACEHOLDERS.contains(&name) => return placeholders::current().get(name),
+        _ => return None,
+    };
+    Some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn status_styles_accept_text_and_tables() {
+        let cfg = Config::parse(
+            r#"
+            [status]
+            waiting = "READY"
+
+            [status.error]
+            icon = "!"
+            color = "red"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(cfg.status.text(Status::Waiting), "READY");
+        assert_eq!(cfg.status.text(Status::Error), "ERROR");
+        assert_eq!(cfg.status.style(Status::Error).icon.as_deref(), Some("!"));
+        assert_eq!(cfg.status.style(Status::Error).colour.as_deref(), Some("red"));
+        assert_eq!(cfg.status.text(Status::Idle), "IDLE");
+    }
+
+    const WITH_PROFILES: &str = r#"
+        [general]
+        notification-title = "Agent"
+        notification-body = "Done"
+
+        [hooks]
+        error = "echo error"
+
+        [profile.work.general]
+        notification-body = "Done at work"
+
+        [profile.work.watchdog]
+        stall-timeout = 60
+    "#;
+
+    #[test]
+    fn profile_overrides_individual_keys() {
+        let cfg = Config::parse_with_profile(WITH_PROFILES, Some("work".to_string())).unwrap();
+        assert_eq!(cfg.profile.as_deref(), Some("work"));
+        assert_eq!(cfg.general.notification_title, "Agent");
+        assert_eq!(cfg.general.notification_body, "Done at work");
+        assert_eq!(cfg.hooks.error.as_deref(), Some("echo error"));
+        assert_eq!(cfg.watchdog.stall_timeout, 60);
+    }
+
+    #[test]
+    fn unknown_profile_is_not_applied() {
+        let cfg = Config::parse_with_profile(WITH_PROFILES, Some("home".to_string())).unwrap();
+        assert_eq!(cfg.profile, None);
+        assert_eq!(cfg.general.notification_body, "Done");
+        assert_eq!(cfg.watchdog.stall_timeout, default_stall_timeout());
+    }
+
+    #[test]
+    fn check_reports_unmatched_closing_braces() {
+        let cfg = Config::parse_with_profile(
+            r#"
+            [hooks]
+            status-change = "jq -n '{a:{b:1}}' > /tmp/{status}.json"
+            "#,
+            None,
+        )
+        .unwrap();
+        let hook = cfg.hooks.status_change.as_deref().unwrap();
+        assert_eq!(resolve_hook(hook, &[("status", "IDLE")]), "jq -n '{a:{b:1}}' > /tmp/IDLE.json");
+        assert_eq!(
+            cfg.check(),
+            ["hooks.status-change: '}}' at byte 14 closes no '{{' and is kept as two braces; \
+              write '}' if one was meant"]
+        );
+    }
 }
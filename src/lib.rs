@@ -1,3 +1,14 @@
+pub mod agent;
+pub mod args;
+pub mod bar;
 pub mod config;
+pub mod log;
 pub mod monitor;
+pub mod mux;
+pub mod placeholders;
+pub mod prompt;
+pub mod registry;
 pub mod state;
+pub mod template;
+pub mod term;
+pub mod tmux;
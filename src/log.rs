@@ -0,0 +1,55 @@
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::sync::{LazyLock, Mutex, OnceLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Log file opened from `CURSOR_WRAPPER_LOG_FILE`, or `None` when logging is
+/// disabled. The file is appended to so the wrapper and backend can share it.
+static LOG_FILE: LazyLock<Option<Mutex<File>>> = LazyLock::new(|| {
+    let path = std::env::var("CURSOR_WRAPPER_LOG_FILE").ok()?;
+    if path.is_empty() {
+        return None;
+    }
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .ok()
+        .map(Mutex::new)
+});
+
+/// Session label added to every line, so logs of several wrappers sharing
+/// one file can be told apart.
+static SESSION: OnceLock<String> = OnceLock::new();
+
+/// Label the following log lines with `name`. Only the first call has an
+/// effect.
+pub fn set_session(name: &str) {
+    let _ = SESSION.set(name.to_string());
+}
+
+/// Append a timestamped line to the log file, if one is configured.
+///
+/// Never writes to stdout/stderr: the terminal belongs to the wrapped CLI.
+pub fn info(msg: impl AsRef<str>) {
+    let Some(file) = LOG_FILE.as_ref() else {
+        return;
+    };
+    let ts = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let pid = std::process::id();
+    let tag = match SESSION.get() {
+        Some(name) => format!("{pid} {name}"),
+        None => pid.to_string(),
+    };
+    if let Ok(mut f) = file.lock() {
+        let _ = writeln!(
+            f,
+            "[{}.{:03}] [{tag}] {}",
+            ts.as_secs(),
+            ts.subsec_millis(),
+            msg.as_ref()
+        );
+    }
+}
@@ -1,4 +1,7 @@
-use cursor_cli_wrapper::{config, monitor, state};
+use cursor_cli_wrapper::args::WrapperArgs;
+use cursor_cli_wrapper::mux::{self, Mux};
+use cursor_cli_wrapper::state::Status;
+use cursor_cli_wrapper::{agent, config, log, monitor, placeholders, prompt, registry, state, term, tmux};
 use std::io::IsTerminal;
 use std::os::fd::AsRawFd;
 use std::sync::{Arc, RwLock};
@@ -6,10 +9,21 @@ use std::time::Duration;
 use tokio::fs::OpenOptions;
 use tokio::io::{self, AsyncReadExt, AsyncWriteExt};
 use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::mpsc;
+
+/// Send a desktop notification without waiting for `notify-send`.
+fn notify(args: Vec<String>) {
+    let _ = tokio::process::Command::new("notify-send")
+        .args(&args)
+        .spawn();
+}
 
 #[tokio::main]
 async fn main() {
-    let args: Vec<String> = std::env::args().skip(1).collect();
+    let (wrapper_args, args) = WrapperArgs::parse(std::env::args().skip(1)).unwrap_or_else(|e| {
+        eprintln!("cursor-cli-wrapper: {e}");
+        std::process::exit(1);
+    });
 
     let (pty, pts) = pty_process::open().unwrap_or_else(|e| {
         eprintln!("failed to create pty: {e}");
@@ -24,11 +38,32 @@ async fn main() {
     // Save raw fd for SIGWINCH resize (valid as long as pty halves live)
     let pty_raw_fd = pty.as_raw_fd();
 
-    // FIXME: support overriding the cursor-agent path via an env var
-    //        (e.g. CURSOR_AGENT_PATH)
-    let cursor_agent_bin = dirs::home_dir()
-        .expect("could not determine home directory")
-        .join(".local/bin/cursor-agent");
+    // Label the session with --wrapper-name, or after the directory it
+    // was started in
+    let session_name = wrapper_args.name.clone().unwrap_or_else(|| {
+        std::env::current_dir()
+            .ok()
+            .and_then(|d| d.file_name().map(|n| n.to_string_lossy().into_owned()))
+            .unwrap_or_else(|| "cursor-agent".to_string())
+    });
+    log::set_session(&session_name);
+    state::update_session(|s| s.name = session_name);
+
+    // Load config into shared state (the watcher starts further down),
+    // with --wrapper-profile taking precedence over $CURSOR_WRAPPER_PROFILE
+    if let Some(ref profile) = wrapper_args.profile {
+        config::select_profile(profile);
+    }
+    let cfg = Arc::new(RwLock::new(config::Config::load()));
+
+    let agent_path = cfg.read().unwrap().general.agent_path.clone();
+    let cursor_agent_bin = agent::Locations::current(wrapper_args.agent_path.clone(), agent_path)
+        .resolve()
+        .unwrap_or_else(|e| {
+            eprintln!("{e}");
+            std::process::exit(1);
+        });
+    log::info(format!("agent: {}", cursor_agent_bin.display()));
 
     let mut child = pty_process::Command::new(&cursor_agent_bin)
         .args(&args)
@@ -38,8 +73,56 @@ async fn main() {
             std::process::exit(1);
         });
 
+    // Spinners change between agent releases; pick the matching profile
+    // once `--version` answers, without holding up the agent's startup.
+    // Until then every known spinner counts.
+    let (detection_tx, mut detection_rx) = tokio::sync::oneshot::channel();
+    {
+        let bin = cursor_agent_bin.clone();
+        let overrides = cfg.read().unwrap().detection.clone();
+        tokio::spawn(async move {
+            let agent_version = agent::query_version(&bin).await;
+            let detection = monitor::Detection::select(agent_version.as_deref(), &overrides);
+            log::info(format!(
+                "agent version: {}, detection profile: {}",
+                agent_version.as_deref().unwrap_or("unknown"),
+                detection.name
+            ));
+            let _ = detection_tx.send(detection);
+        });
+    }
+
+    // Capture our pane before anything else talks to the multiplexer, so
+    // every option is set on this pane only.
+    match mux::current() {
+        Some(Mux::Tmux) => log::info(format!("tmux pane: {}", tmux::pane().unwrap_or("?"))),
+        Some(mux) => log::info(format!("multiplexer: {}", mux.as_str())),
+        None => {}
+    }
+
+    // Resolve placeholders ({cwd}, {git_branch}, ...) against the agent's
+    // working directory, and warm the cache in the background.
+    if let Some(pid) = child.id() {
+        placeholders::set_agent_pid(pid);
+    }
+    tokio::spawn(async {
+        placeholders::refresh().await;
+        registry::update();
+    });
+
     let (mut pty_reader, mut pty_writer) = pty.into_split();
 
+    // Single writer for the PTY, so both the stdin relay and the wrapper
+    // itself (e.g. re-sending a prompt after an error) can send input.
+    let (pty_tx, mut pty_rx) = mpsc::unbounded_channel::<Vec<u8>>();
+    tokio::spawn(async move {
+        while let Some(data) = pty_rx.recv().await {
+            if pty_writer.write_all(&data).await.is_err() {
+                break;
+            }
+        }
+    });
+
     // Enable raw mode so keypresses are forwarded immediately
     let is_tty = std::io::stdin().is_terminal();
     if is_tty {
@@ -68,14 +151,17 @@ async fn main() {
         }
     });
 
-    // Load config into shared state and spawn file watcher
-    let cfg = Arc::new(RwLock::new(config::Config::load()));
+    // Reload the config when it changes
     {
         let cfg = Arc::clone(&cfg);
         tokio::spawn(config::watch_config(cfg));
     }
 
-    state::set_tmux_status("IDLE", cfg.read().unwrap().hooks.status_change.as_deref());
+    state::set_profile(cfg.read().unwrap().profile.clone());
+    state::set_mux_option("@ai-agent-session", &state::session().name);
+    state::set_mux_option("@ai-agent-vim-mode", state::get_vim_mode().as_str());
+    state::set_status(Some(Status::Idle), &cfg.read().unwrap());
+    registry::enable();
 
     // Optionally dump all raw stdin input to a file (for debugging keypresses)
     let mut input_dump_file = match std::env::var("CURSOR_WRAPPER_INPUT_DUMP_FILE") {
@@ -96,9 +182,11 @@ async fn main() {
 
     // Relay stdin -> PTY
     let stdin_cfg = Arc::clone(&cfg);
+    let stdin_pty_tx = pty_tx.clone();
     let _stdin_task = tokio::spawn(async move {
         let mut stdin = io::stdin();
         let mut buf = [0u8; 4096];
+        let mut prompt_tracker = prompt::PromptTracker::new();
         loop {
             let n = match stdin.read(&mut buf).await {
                 Ok(0) | Err(_) => break,
@@ -135,7 +223,7 @@ async fn main() {
             // not part of an Alt+I sequence are preserved and forwarded
             // normally.
             let data = if has_alt_i {
-                state::set_tmux_status("IDLE", cfg_snapshot.hooks.status_change.as_deref());
+                state::set_status(Some(Status::Idle), &cfg_snapshot);
                 let mut filtered = Vec::with_capacity(n);
                 let mut i = 0;
                 while i < data.len() {
@@ -160,12 +248,21 @@ async fn main() {
             // Neovim) both count as an ESC keypress.
             let is_esc =
                 (n == 1 && data[0] == ESC) || data.windows(CSI_U_ESC.len()).any(|w| w == CSI_U_ESC);
-            if is_esc && state::get_vim_mode() == state::VimMode::Normal {
-                if let Some(ref cmd) = cfg_snapshot.hooks.esc_in_normal {
-                    state::run_hook(cmd);
+            if is_esc
+                && state::get_vim_mode() == state::VimMode::Normal
+                && let Some(ref cmd) = cfg_snapshot.hooks.esc_in_normal
+            {
+                state::run_hook(cmd, &[]);
+            }
+            match prompt_tracker.feed(data, state::get_vim_mode()) {
+                Some(prompt::Submitted::Prompt(prompt)) => {
+                    state::update_session(|s| s.last_prompt = Some(prompt));
                 }
+                // Never retry with a prompt other than the one submitted
+                Some(prompt::Submitted::Unknown) => state::update_session(|s| s.last_prompt = None),
+                None => {}
             }
-            if !data.is_empty() && pty_writer.write_all(data).await.is_err() {
+            if !data.is_empty() && stdin_pty_tx.send(data.to_vec()).is_err() {
                 break;
             }
         }
@@ -194,8 +291,25 @@ async fn main() {
         let mut stdout = io::stdout();
         let mut buf = [0u8; 4096];
         let mut monitor = monitor::OutputMonitor::new();
+        // Automatic re-sends of the last prompt since the last successful turn
+        let mut retries = 0;
+        // Whether the watchdog has flagged the current turn as STALLED
+        let mut stalled = false;
+        // Where the output is relative to escape sequences, so our own
+        // (title, user vars) are only written outside them
+        let mut boundary = term::Boundary::default();
 
         loop {
+            if boundary.at_boundary() {
+                let pending = term::take_pending();
+                if !pending.is_empty() {
+                    if stdout.write_all(&pending).await.is_err() {
+                        break;
+                    }
+                    let _ = stdout.flush().await;
+                }
+            }
+
             // Use a timeout so we can check for state transitions
             // even when no new data arrives from the PTY.
             let result =
@@ -205,16 +319,79 @@ async fn main() {
                 Ok(Ok(0)) | Ok(Err(_)) => break,
                 Ok(Ok(n)) => {
                     let chunk = &buf[..n];
+                    if let Ok(detection) = detection_rx.try_recv() {
+                        monitor.set_detection(detection);
+                    }
+                    monitor.set_error_patterns(&stdout_cfg.read().unwrap().errors.patterns);
                     let result = monitor.process_chunk(chunk);
                     if result.entered_busy {
-                        let hook = stdout_cfg.read().unwrap().hooks.status_change.clone();
-                        state::set_tmux_status("INPROGRESS", hook.as_deref());
+                        // New turn: recompute placeholder values off the relay task
+                        state::begin_turn();
+                        tokio::spawn(async {
+                            placeholders::refresh().await;
+                            registry::update();
+                        });
+                        state::set_status(Some(Status::InProgress), &stdout_cfg.read().unwrap());
+                    }
+                    if let Some(model) = result.model_changed {
+                        log::info(format!("model: {model}"));
+                        state::set_mux_option("@ai-agent-model", &model);
+                        state::update_session(|s| s.model = model);
+                        registry::update();
+                    }
+                    if let Some(phase) = result.phase_changed {
+                        state::set_mux_option("@ai-agent-phase", &phase);
+                        let previous = state::session().phase;
+                        let sub = Status::from_phase(&phase);
+                        if previous.map(|p| Status::from_phase(&p)) != Some(sub) {
+                            let text = stdout_cfg.read().unwrap().status.text(sub);
+                            state::set_mux_option("@ai-agent-substatus", &text);
+                        }
+                        state::update_session(|s| s.phase = Some(phase));
+                    }
+                    if let Some(tokens) = result.tokens_changed {
+                        state::set_mux_option("@ai-agent-tokens", &state::format_count(tokens));
+                        state::update_session(|s| s.tokens = Some(tokens));
+                    }
+                    if let Some(pct) = result.context_changed {
+                        state::set_mux_option("@ai-agent-context", &format!("{pct}%"));
+                        let previous = state::session().context;
+                        state::update_session(|s| s.context = Some(pct));
+
+                        // Warn once when usage crosses the threshold; re-arm
+                        // after it drops below again (compaction / restart).
+                        let cfg_snapshot = stdout_cfg.read().unwrap().clone();
+                        if let Some(threshold) = cfg_snapshot.general.context_warning_threshold {
+                            let was_over = previous.is_some_and(|p| p >= threshold);
+                            if pct >= threshold && !was_over {
+                                log::info(format!("context at {pct}% (threshold {threshold}%)"));
+                                if let Some(ref cmd) = cfg_snapshot.hooks.context_warning {
+                                    state::run_hook(cmd, &[]);
+                                }
+                                let general = &cfg_snapshot.general;
+                                let args = general.notify_send_args_for(
+                                    &general.context_warning_title,
+                                    &general.context_warning_body,
+                                    general.context_warning_urgency,
+                                );
+                                notify(args);
+                            }
+                        }
+                    }
+                    if let Some(mode) = result.agent_mode_changed {
+                        log::info(format!("agent mode: {}", mode.as_str()));
+                        state::set_mux_option("@ai-agent-mode", mode.as_str());
+                        let hook = stdout_cfg.read().unwrap().hooks.agent_mode_change.clone();
+                        if let Some(cmd) = hook {
+                            state::run_hook(&cmd, &[("agent_mode", mode.as_str())]);
+                        }
                     }
                     if let Some(mode) = result.vim_mode_changed {
+                        state::set_mux_option("@ai-agent-vim-mode", mode.as_str());
+                        registry::update();
                         let hook = stdout_cfg.read().unwrap().hooks.vim_mode_change.clone();
                         if let Some(cmd) = hook {
-                            let cmd = cmd.replace("{vim_mode}", mode.as_str());
-                            state::run_hook(&cmd);
+                            state::run_hook(&cmd, &[("vim_mode", mode.as_str())]);
                         }
                     }
 
@@ -222,6 +399,7 @@ async fn main() {
                         break;
                     }
                     let _ = stdout.flush().await;
+                    boundary.feed(chunk);
 
                     // Dump raw output to file when configured
                     if let Some(ref mut f) = dump_file {
@@ -234,14 +412,94 @@ async fn main() {
                 }
             }
 
-            if monitor.check_transition() {
-                // Agent finished generating/thinking — fire notification
-                let cfg_snapshot = stdout_cfg.read().unwrap().clone();
-                state::set_tmux_status("WAITING", cfg_snapshot.hooks.status_change.as_deref());
-                let args = cfg_snapshot.general.notify_send_args();
-                let _ = tokio::process::Command::new("notify-send")
-                    .args(&args)
-                    .spawn();
+            // Stuck-agent watchdog: no new output besides the spinner
+            if let Some(quiet) = monitor.stalled_for() {
+                let timeout = stdout_cfg.read().unwrap().watchdog.timeout();
+                let is_stalled = timeout.is_some_and(|t| quiet >= t);
+                if is_stalled && !stalled {
+                    let cfg_snapshot = stdout_cfg.read().unwrap().clone();
+                    log::info(format!("no progress for {}s, agent stalled", quiet.as_secs()));
+                    state::set_status(Some(Status::Stalled), &cfg_snapshot);
+                    if let Some(ref cmd) = cfg_snapshot.hooks.stalled {
+                        state::run_hook(cmd, &[]);
+                    }
+                    if cfg_snapshot.watchdog.interrupt {
+                        let _ = pty_tx.send(vec![0x03]);
+                    }
+                    tokio::spawn(async move {
+                        placeholders::refresh().await;
+                        let watchdog = &cfg_snapshot.watchdog;
+                        notify(cfg_snapshot.general.notify_send_args_for(
+                            &watchdog.notification_title,
+                            &watchdog.notification_body,
+                            config::Urgency::Critical,
+                        ));
+                    });
+                } else if !is_stalled && stalled {
+                    log::info("agent made progress again");
+                    state::set_status(Some(Status::InProgress), &stdout_cfg.read().unwrap());
+                }
+                stalled = is_stalled;
+            }
+
+            let Some(turn_end) = monitor.check_transition() else {
+                continue;
+            };
+            stalled = false;
+            state::end_turn();
+            state::unset_mux_option("@ai-agent-phase");
+            state::unset_mux_option("@ai-agent-substatus");
+            state::unset_mux_option("@ai-agent-tokens");
+            let cfg_snapshot = stdout_cfg.read().unwrap().clone();
+
+            match turn_end {
+                monitor::TurnEnd::Done => {
+                    // Agent finished generating/thinking — fire notification
+                    retries = 0;
+                    state::set_status(Some(Status::Waiting), &cfg_snapshot);
+                    tokio::spawn(async move {
+                        placeholders::refresh().await;
+                        notify(cfg_snapshot.general.notify_send_args());
+                    });
+                }
+                monitor::TurnEnd::Error(line) => {
+                    log::info(format!("turn {} ended with an error: {line}", state::session().turn));
+                    state::update_session(|s| s.error = Some(line));
+                    state::set_status(Some(Status::Error), &cfg_snapshot);
+                    if let Some(ref cmd) = cfg_snapshot.hooks.error {
+                        state::run_hook(cmd, &[]);
+                    }
+
+                    let errors = &cfg_snapshot.errors;
+                    let prompt = state::session().last_prompt;
+                    if let Some(prompt) = prompt.filter(|_| retries < errors.retry_attempts) {
+                        retries += 1;
+                        let backoff = Duration::from_secs(errors.retry_backoff);
+                        let tx = pty_tx.clone();
+                        tokio::spawn(async move {
+                            tokio::time::sleep(backoff).await;
+                            // The user moved on (new turn, Alt+I) meanwhile
+                            if state::session().status != Some(Status::Error) {
+                                return;
+                            }
+                            log::info(format!("re-sending last prompt (attempt {retries})"));
+                            let mut data = b"\x1b[200~".to_vec();
+                            data.extend_from_slice(prompt.as_bytes());
+                            data.extend_from_slice(b"\x1b[201~\r");
+                            let _ = tx.send(data);
+                        });
+                    }
+
+                    tokio::spawn(async move {
+                        placeholders::refresh().await;
+                        let errors = &cfg_snapshot.errors;
+                        notify(cfg_snapshot.general.notify_send_args_for(
+                            &errors.notification_title,
+                            &errors.notification_body,
+                            config::Urgency::Critical,
+                        ));
+                    });
+                }
             }
         }
     });
@@ -261,8 +519,18 @@ async fn main() {
         let _ = crossterm::terminal::disable_raw_mode();
     }
 
-    // Clear tmux status on exit
-    state::set_tmux_status("", cfg.read().unwrap().hooks.status_change.as_deref());
+    // Clear status, pane options, title and user vars on exit
+    state::set_status(None, &cfg.read().unwrap());
+    state::clear_mux_options();
+    registry::remove();
+    tmux::flush();
+    mux::flush();
+    {
+        use std::io::Write;
+        let mut stdout = std::io::stdout();
+        let _ = stdout.write_all(&term::take_pending());
+        let _ = stdout.flush();
+    }
 
     std::process::exit(status.code().unwrap_or(1));
 }
@@ -1,10 +1,17 @@
-use crate::state::{self, VimMode};
+use crate::{agent, config, log};
+use crate::state::{self, AgentMode, VimMode};
 use regex::bytes::Regex;
+use std::collections::VecDeque;
+use std::hash::{DefaultHasher, Hash, Hasher};
 use std::sync::LazyLock;
 use std::time::{Duration, Instant};
 
 const DEBOUNCE: Duration = Duration::from_secs(1);
 
+/// Number of recent screen fingerprints remembered by the stall watchdog.
+/// Redraws of any of these do not count as progress.
+const RECENT_SCREENS: usize = 64;
+
 /// Regex matching the vim NORMAL mode cursor styling:
 /// ESC[100m {any char} ESC[49m
 static NORMAL_MODE_RE: LazyLock<Regex> =
@@ -15,25 +22,204 @@ static NORMAL_MODE_RE: LazyLock<Regex> =
 static INSERT_MODE_RE: LazyLock<Regex> =
     LazyLock::new(|| Regex::new(r"\x1b\[7m.\x1b\[27m").unwrap());
 
-/// Regex matching two consecutive Braille Pattern chars (U+2800–U+28FF).
-/// In UTF-8 each is \xe2[\xa0-\xa3][\x80-\xbf].
-static BRAILLE_SPINNER_RE: LazyLock<Regex> = LazyLock::new(|| {
-    Regex::new(r"(?-u)\xe2[\xa0-\xa3][\x80-\xbf]\xe2[\xa0-\xa3][\x80-\xbf]").unwrap()
+/// Regex matching the agent footer line, e.g. `Claude 4.6 Opus (Thinking) · 8.3%`,
+/// directly above the `/ commands · @ files` hints, so that prose in an
+/// answer (`Coverage · 85%`) is not taken for it. Group 1 is the model
+/// name, group 2 the context-window usage percentage.
+static FOOTER_RE: LazyLock<regex::Regex> = LazyLock::new(|| {
+    regex::Regex::new(
+        r"(?m)^[ \t]*([^\s·][^·\n]*?)[ \t]+·[ \t]+(\d+(?:\.\d+)?)%[ \t]*\r?\n[ \t]*/ commands\b",
+    )
+    .unwrap()
 });
 
-/// Check whether the raw PTY output contains a busy indicator.
-///
-/// Detects (on ANSI-stripped text):
-/// - Braille spinner (two consecutive braille-pattern chars) — current Cursor indicator.
-/// - Bullet spinner `•` (U+2022) — previous Cursor indicator.
-/// - Hexagon spinners `⬢` (U+2B22) / `⬡` (U+2B21) — legacy indicator.
-fn is_busy(raw: &[u8]) -> bool {
-    if BRAILLE_SPINNER_RE.is_match(raw) {
-        return true;
+/// Every spinner Cursor has used: Braille pairs (current), the bullet `•`
+/// (previous) and the hexagons `⬢`/`⬡` (legacy).
+const ANY_SPINNER: &str = r"[\x{2800}-\x{28FF}]{2}|[\x{2022}\x{2B21}\x{2B22}]";
+
+/// Phase names the agent shows next to the spinner. Only these count, so
+/// a list item such as `• Added tests` in an answer is not a phase.
+const PHASES: &[&str] = &[
+    "Thinking",
+    "Generating",
+    "Reading",
+    "Editing",
+    "Writing",
+    "Running",
+    "Searching",
+    "Listing",
+    "Grepping",
+    "Fetching",
+    "Planning",
+];
+
+/// Built-in detection profiles as (name, versions, spinner), tried in
+/// order after the `[[detection]]` ones from the config. Only add a
+/// version-specific entry backed by a captured shot of that version under
+/// `shots/`; until then `any-spinner` covers every release.
+const BUILTIN_DETECTION: &[(&str, &str, &str)] = &[("any-spinner", "..", ANY_SPINNER)];
+
+/// How the agent shows it is busy, which changes between releases.
+#[derive(Debug, Clone)]
+pub struct Detection {
+    pub name: String,
+    /// The spinner followed by one of the [`PHASES`], in ANSI-stripped
+    /// output. A glyph on its own, or a `• Added tests` list item, is not
+    /// enough.
+    busy: regex::Regex,
+    /// The busy line next to the spinner, e.g. `⬡ Thinking...  202 tokens`
+    /// or `⡕⡰ Generating`. Group 1 is one of the [`PHASES`], group 2 the
+    /// (optional) live token count.
+    phase: regex::Regex,
+}
+
+impl Default for Detection {
+    fn default() -> Self {
+        let (name, _, spinner) = BUILTIN_DETECTION[BUILTIN_DETECTION.len() - 1];
+        Self::new(name, spinner).expect("built-in spinner regex")
+    }
+}
+
+impl Detection {
+    pub fn new(name: &str, spinner: &str) -> Result<Self, regex::Error> {
+        let busy = format!(r"(?:{spinner})[ \t]+(?:{})\b", PHASES.join("|"));
+        let phase = format!(
+            r"(?:{spinner})[ \t]+({})\b\.{{0,3}}(?:[ \t]+([\d,]+)[ \t]+tokens)?",
+            PHASES.join("|")
+        );
+        Ok(Self {
+            name: name.to_string(),
+            busy: regex::Regex::new(&busy)?,
+            phase: regex::Regex::new(&phase)?,
+        })
+    }
+
+    /// The first profile, from `overrides` then the built-in ones, whose
+    /// version range contains `version` (the output of
+    /// `cursor-agent --version`). Invalid profiles are logged and skipped.
+    pub fn select(version: Option<&str>, overrides: &[config::DetectionProfile]) -> Self {
+        let version = version.and_then(agent::parse_version);
+        let builtin = BUILTIN_DETECTION.iter().map(|&(name, versions, spinner)| {
+            (name, Some(versions), spinner)
+        });
+        let profiles = overrides
+            .iter()
+            .map(|p| (p.name.as_str(), p.versions.as_deref(), p.spinner.as_str()))
+            .chain(builtin);
+        for (name, versions, spinner) in profiles {
+            let range = match versions.map(agent::VersionRange::parse) {
+                None => agent::VersionRange::ANY,
+                Some(Ok(range)) => range,
+                Some(Err(e)) => {
+                    log::info(format!("ignoring detection profile '{name}': {e}"));
+                    continue;
+                }
+            };
+            if !range.contains(version.as_deref()) {
+                continue;
+            }
+            match Self::new(name, spinner) {
+                Ok(detection) => return detection,
+                Err(e) => log::info(format!("ignoring detection profile '{name}': {e}")),
+            }
+        }
+        Self::default()
+    }
+
+    /// Whether ANSI-stripped output shows the busy spinner.
+    fn is_busy(&self, text: &str) -> bool {
+        self.busy.is_match(text)
+    }
+
+    /// Extract the busy phase name and token count from ANSI-stripped text.
+    fn parse_phase<'a>(&self, text: &'a str) -> Option<(&'a str, Option<u64>)> {
+        let caps = self.phase.captures_iter(text).last()?;
+        let phase = caps.get(1)?.as_str();
+        let tokens = caps
+            .get(2)
+            .and_then(|m| m.as_str().replace(',', "").parse().ok());
+        Some((phase, tokens))
+    }
+}
+
+/// Fingerprint of ANSI-stripped text for the stall watchdog, ignoring the
+/// parts that animate without progress: spinner glyphs, dot animations and
+/// whitespace. Returns `None` if nothing is left.
+fn screen_fingerprint(text: &str) -> Option<u64> {
+    let mut hasher = DefaultHasher::new();
+    let mut empty = true;
+    for c in text.chars() {
+        let animated = matches!(c, '\u{2800}'..='\u{28FF}' | '\u{2022}' | '\u{2B21}' | '\u{2B22}' | '.');
+        if animated || c.is_whitespace() {
+            continue;
+        }
+        c.hash(&mut hasher);
+        empty = false;
     }
+    (!empty).then(|| hasher.finish())
+}
+
+/// Find the last line of ANSI-stripped text matching any of `patterns`,
+/// trimmed of whitespace and box-drawing borders.
+fn find_error_line<'a>(patterns: &[regex::Regex], text: &'a str) -> Option<&'a str> {
+    text.lines()
+        .rev()
+        .find(|line| patterns.iter().any(|re| re.is_match(line)))
+        .map(|line| line.trim_matches(|c: char| c.is_whitespace() || c == '│'))
+}
+
+/// Regex matching the agent mode line, e.g. `◎ Ask (shift+tab to cycle)`.
+static AGENT_MODE_RE: LazyLock<regex::Regex> =
+    LazyLock::new(|| regex::Regex::new(r"\b(Ask|Agent|Plan)\s+\(shift\+tab to cycle\)").unwrap());
+
+/// Extract the agent mode from ANSI-stripped text.
+fn parse_agent_mode(text: &str) -> Option<AgentMode> {
+    AGENT_MODE_RE
+        .captures_iter(text)
+        .last()
+        .and_then(|c| AgentMode::from_label(&c[1]))
+}
+
+/// Extract the context-window usage percentage from ANSI-stripped footer text.
+fn parse_context(text: &str) -> Option<f32> {
+    FOOTER_RE
+        .captures_iter(text)
+        .last()
+        .and_then(|c| c.get(2))
+        .and_then(|m| m.as_str().parse().ok())
+}
+
+/// Extract the active model name from ANSI-stripped footer text.
+fn parse_model(text: &str) -> Option<&str> {
+    FOOTER_RE
+        .captures_iter(text)
+        .last()
+        .and_then(|c| c.get(1))
+        .map(|m| m.as_str().trim())
+}
+
+/// Check whether the raw PTY output contains a busy indicator, with the
+/// default detection profile.
+#[cfg(test)]
+fn is_busy(raw: &[u8]) -> bool {
     let stripped = strip_ansi_escapes::strip(raw);
-    let text = String::from_utf8_lossy(&stripped);
-    text.contains('\u{2B22}') || text.contains('\u{2B21}')
+    Detection::default().is_busy(&String::from_utf8_lossy(&stripped))
+}
+
+#[cfg(test)]
+fn parse_phase(text: &str) -> Option<(&str, Option<u64>)> {
+    static DEFAULT: LazyLock<Detection> = LazyLock::new(Detection::default);
+    DEFAULT.parse_phase(text)
+}
+
+/// How a turn ended, reported by [`OutputMonitor::check_transition`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TurnEnd {
+    /// The agent finished normally.
+    Done,
+    /// An error banner matched one of the error patterns while the agent
+    /// was busy; holds the matching line.
+    Error(String),
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -48,31 +234,106 @@ pub struct ChunkResult {
     pub entered_busy: bool,
     /// Set when the vim mode changed compared to the previous chunk.
     pub vim_mode_changed: Option<VimMode>,
+    /// Set when the footer shows a different model than before.
+    pub model_changed: Option<String>,
+    /// Set when the footer shows a different context-window usage (percent).
+    pub context_changed: Option<f32>,
+    /// Set when the agent mode changed compared to the last one seen.
+    pub agent_mode_changed: Option<AgentMode>,
+    /// Set while busy when the phase next to the spinner changes
+    /// (e.g. `Thinking` -> `Generating`).
+    pub phase_changed: Option<String>,
+    /// Set while busy when the live token count changes.
+    pub tokens_changed: Option<u64>,
 }
 
 pub struct OutputMonitor {
+    detection: Detection,
     state: AgentState,
     last_busy_seen: Instant,
     last_vim_mode: VimMode,
+    last_model: Option<String>,
+    last_context: Option<f32>,
+    last_agent_mode: Option<AgentMode>,
+    last_phase: Option<String>,
+    last_tokens: Option<u64>,
+    /// Sources of `error_patterns`, to skip recompiling unchanged patterns.
+    error_sources: Vec<String>,
+    error_patterns: Vec<regex::Regex>,
+    /// Error line seen during the current turn.
+    last_error: Option<String>,
+    /// Fingerprints of recently seen screens, see [`screen_fingerprint`].
+    recent_screens: VecDeque<u64>,
+    /// Last time the output showed something new while busy.
+    last_progress: Instant,
+}
+
+impl Default for OutputMonitor {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl OutputMonitor {
     pub fn new() -> Self {
         Self {
+            detection: Detection::default(),
             state: AgentState::Idle,
             last_busy_seen: Instant::now(),
             last_vim_mode: VimMode::Insert,
+            last_model: None,
+            last_context: None,
+            last_agent_mode: None,
+            last_phase: None,
+            last_tokens: None,
+            error_sources: Vec::new(),
+            error_patterns: Vec::new(),
+            last_error: None,
+            recent_screens: VecDeque::with_capacity(RECENT_SCREENS),
+            last_progress: Instant::now(),
         }
     }
 
+    /// Use `detection` to recognise the busy spinner and phase.
+    pub fn set_detection(&mut self, detection: Detection) {
+        self.detection = detection;
+    }
+
+    /// Set the regexes that identify error screens. Invalid patterns are
+    /// logged and skipped.
+    pub fn set_error_patterns(&mut self, patterns: &[String]) {
+        if self.error_sources == patterns {
+            return;
+        }
+        self.error_sources = patterns.to_vec();
+        self.error_patterns = patterns
+            .iter()
+            .filter_map(|p| match regex::Regex::new(p) {
+                Ok(re) => Some(re),
+                Err(e) => {
+                    log::info(format!("ignoring invalid error pattern {p:?}: {e}"));
+                    None
+                }
+            })
+            .collect();
+    }
+
     /// Scan a raw PTY output chunk for busy patterns and vim mode changes.
     /// Strips ANSI escape codes before matching.
     pub fn process_chunk(&mut self, raw: &[u8]) -> ChunkResult {
         // Detect vim mode changes from cursor styling escape sequences.
         let vim_mode_changed = self.detect_vim_mode(raw);
 
-        let entered_busy = if is_busy(raw) {
+        let stripped = strip_ansi_escapes::strip(raw);
+        let text = String::from_utf8_lossy(&stripped);
+
+        let entered_busy = if self.detection.is_busy(&text) {
             let entered = self.state == AgentState::Idle;
+            if entered {
+                self.last_error = None;
+                self.recent_screens.clear();
+                self.last_progress = Instant::now();
+            }
             self.state = AgentState::Busy;
             self.last_busy_seen = Instant::now();
             entered
@@ -80,10 +341,109 @@ impl OutputMonitor {
             false
         };
 
+        let model_changed = self.detect_model(&text);
+        let context_changed = self.detect_context(&text);
+        let agent_mode_changed = self.detect_agent_mode(&text);
+        let (phase_changed, tokens_changed) = if self.state == AgentState::Busy {
+            self.track_progress(&text);
+            self.detect_error(&text);
+            self.detect_phase(&text)
+        } else {
+            (None, None)
+        };
+
         ChunkResult {
             entered_busy,
             vim_mode_changed,
+            model_changed,
+            context_changed,
+            agent_mode_changed,
+            phase_changed,
+            tokens_changed,
+        }
+    }
+
+    /// Note progress when the output differs from every recently seen
+    /// screen (ignoring spinner animation).
+    fn track_progress(&mut self, text: &str) {
+        let Some(fp) = screen_fingerprint(text) else {
+            return;
+        };
+        if self.recent_screens.contains(&fp) {
+            return;
         }
+        if self.recent_screens.len() == RECENT_SCREENS {
+            self.recent_screens.pop_front();
+        }
+        self.recent_screens.push_back(fp);
+        self.last_progress = Instant::now();
+    }
+
+    /// While busy, how long the output has shown nothing but spinner
+    /// animation and redraws of earlier screens. `None` when idle.
+    pub fn stalled_for(&self) -> Option<Duration> {
+        (self.state == AgentState::Busy).then(|| self.last_progress.elapsed())
+    }
+
+    /// Remember the last line matching one of the error patterns.
+    fn detect_error(&mut self, text: &str) {
+        if let Some(line) = find_error_line(&self.error_patterns, text) {
+            self.last_error = Some(line.to_string());
+        }
+    }
+
+    /// Track the busy phase and token count shown next to the spinner,
+    /// returning whichever of them changed.
+    fn detect_phase(&mut self, text: &str) -> (Option<String>, Option<u64>) {
+        let Some((phase, tokens)) = self.detection.parse_phase(text) else {
+            return (None, None);
+        };
+
+        let phase_changed = if self.last_phase.as_deref() != Some(phase) {
+            self.last_phase = Some(phase.to_string());
+            Some(phase.to_string())
+        } else {
+            None
+        };
+        let tokens_changed = tokens.filter(|t| self.last_tokens != Some(*t));
+        if tokens.is_some() {
+            self.last_tokens = tokens;
+        }
+        (phase_changed, tokens_changed)
+    }
+
+    /// Returns `Some(mode)` when the agent mode line shows a mode different
+    /// from the last one seen.
+    fn detect_agent_mode(&mut self, text: &str) -> Option<AgentMode> {
+        let mode = parse_agent_mode(text)?;
+        state::set_agent_mode(mode);
+        if self.last_agent_mode == Some(mode) {
+            return None;
+        }
+        self.last_agent_mode = Some(mode);
+        Some(mode)
+    }
+
+    /// Returns `Some(percent)` when the footer shows a context-window usage
+    /// different from the last one seen.
+    fn detect_context(&mut self, text: &str) -> Option<f32> {
+        let pct = parse_context(text)?;
+        if self.last_context == Some(pct) {
+            return None;
+        }
+        self.last_context = Some(pct);
+        Some(pct)
+    }
+
+    /// Returns `Some(model)` when the footer shows a model different from
+    /// the last one seen.
+    fn detect_model(&mut self, text: &str) -> Option<String> {
+        let model = parse_model(text)?;
+        if self.last_model.as_deref() == Some(model) {
+            return None;
+        }
+        self.last_model = Some(model.to_string());
+        Some(model.to_string())
     }
 
     /// Detect vim mode transitions from the raw cursor styling sequences
@@ -109,14 +469,19 @@ impl OutputMonitor {
         None
     }
 
-    /// Returns `true` (once) when the agent transitions from Busy to Idle,
+    /// Returns `Some` (once) when the agent transitions from Busy to Idle,
     /// i.e. no busy pattern has been seen for the debounce duration.
-    pub fn check_transition(&mut self) -> bool {
+    pub fn check_transition(&mut self) -> Option<TurnEnd> {
         if self.state == AgentState::Busy && self.last_busy_seen.elapsed() > DEBOUNCE {
             self.state = AgentState::Idle;
-            return true;
+            self.last_phase = None;
+            self.last_tokens = None;
+            return Some(match self.last_error.take() {
+                Some(line) => TurnEnd::Error(line),
+                None => TurnEnd::Done,
+            });
         }
-        false
+        None
     }
 }
 
@@ -163,6 +528,43 @@ mod tests {
         assert!(is_busy("  ⬡ Thinking...  202 tokens".as_bytes()));
     }
 
+    // -- Detection profiles --
+
+    fn profile(name: &str, versions: Option<&str>, spinner: &str) -> config::DetectionProfile {
+        config::DetectionProfile {
+            name: name.to_string(),
+            versions: versions.map(String::from),
+            spinner: spinner.to_string(),
+        }
+    }
+
+    #[test]
+    fn detection_profile_by_version() {
+        // The era profiles from config.toml.example
+        let overrides = [
+            profile("broken", Some("2025.1.."), "("),
+            profile("hexagon", Some("..2025.8"), r"[\x{2B21}\x{2B22}]"),
+            profile("braille", Some("2025.10.."), r"[\x{2800}-\x{28FF}]{2}"),
+        ];
+        let hexagon = Detection::select(Some("2025.07.30-1a2b3c4"), &overrides);
+        assert_eq!(hexagon.name, "hexagon");
+        assert!(hexagon.is_busy("  ⬡ Thinking...  202 tokens"));
+        assert!(!hexagon.is_busy("⡕⡰ Generating"));
+
+        let braille = Detection::select(Some("2025.11.03-abc"), &overrides);
+        assert_eq!(braille.name, "braille");
+        assert!(braille.is_busy("⡕⡰ Generating"));
+        assert!(!braille.is_busy("  • Generating..."));
+        assert_eq!(braille.parse_phase("⡕⡰ Thinking  12 tokens"), Some(("Thinking", Some(12))));
+
+        // Outside every configured range, and for unknown versions, all
+        // spinners count
+        let any = Detection::select(Some("2025.09.18-7ae6800"), &overrides);
+        assert_eq!(any.name, "any-spinner");
+        assert!(any.is_busy("  • Generating..."));
+        assert_eq!(Detection::select(None, &overrides).name, "any-spinner");
+    }
+
     // -- Idle / done states --
 
     #[test]
@@ -177,4 +579,252 @@ mod tests {
         assert!(!is_busy(b"Hello world"));
         assert!(!is_busy(b""));
     }
+
+    #[test]
+    fn lone_spinner_glyph_is_not_busy() {
+        assert!(!is_busy("  Tests pass • 3 files changed".as_bytes()));
+        assert!(!is_busy("  •\n  Thinking".as_bytes()));
+        assert!(!is_busy("  • item one".as_bytes()));
+        assert!(!is_busy("  • Added tests for the parser".as_bytes()));
+        assert!(!is_busy("⡕⡰ Done".as_bytes()));
+    }
+
+    // -- Footer: model name --
+
+    #[test]
+    fn model_from_done_shot() {
+        let raw = include_bytes!("../shots/done/1.txt");
+        let stripped = strip_ansi_escapes::strip(raw);
+        let text = String::from_utf8_lossy(&stripped);
+        assert_eq!(parse_model(&text), Some("Claude 4.6 Opus (Thinking)"));
+    }
+
+    /// The footer as drawn below the input box, for `model`.
+    fn footer(model: &str) -> String {
+        format!("  {model}\n  / commands · @ files · ! shell\n")
+    }
+
+    #[test]
+    fn model_with_integer_percentage() {
+        assert_eq!(
+            parse_model("  Claude 4.6 Opus (Thinking) · 8%\n  / commands · @ files"),
+            Some("Claude 4.6 Opus (Thinking)")
+        );
+        assert_eq!(parse_model(&footer("GPT-5 · 42.5%")), Some("GPT-5"));
+    }
+
+    #[test]
+    fn footer_hints_are_not_a_model() {
+        assert_eq!(parse_model("  / commands · @ files · ! shell"), None);
+    }
+
+    #[test]
+    fn prose_is_not_a_footer() {
+        let answer = "  Coverage · 85%\n  Lines · 1,204 · 97.5%\n\n  The tests pass.";
+        assert_eq!(parse_model(answer), None);
+        assert_eq!(parse_context(answer), None);
+    }
+
+    // -- Busy phase and token count --
+
+    #[test]
+    fn phase_and_tokens_from_thinking_shot() {
+        let raw = strip_ansi_escapes::strip(include_bytes!("../shots/thinking/1.txt"));
+        assert_eq!(
+            parse_phase(&String::from_utf8_lossy(&raw)),
+            Some(("Thinking", Some(202)))
+        );
+    }
+
+    #[test]
+    fn phase_without_tokens() {
+        let raw = strip_ansi_escapes::strip(include_bytes!("../shots/generating/1.txt"));
+        assert_eq!(
+            parse_phase(&String::from_utf8_lossy(&raw)),
+            Some(("Generating", None))
+        );
+        assert_eq!(parse_phase("⡕⡰ Reading"), Some(("Reading", None)));
+        assert_eq!(parse_phase("  • Thinking..  4,210 tokens"), Some(("Thinking", Some(4210))));
+    }
+
+    #[test]
+    fn list_items_are_not_phases() {
+        assert_eq!(parse_phase("  • Added tests for the parser"), None);
+        assert_eq!(parse_phase("  • Readme updated"), None);
+        assert_eq!(parse_phase("  • Running the suite"), Some(("Running", None)));
+    }
+
+    #[test]
+    fn idle_text_has_no_phase() {
+        let raw = strip_ansi_escapes::strip(include_bytes!("../shots/done/1.txt"));
+        assert_eq!(parse_phase(&String::from_utf8_lossy(&raw)), None);
+    }
+
+    #[test]
+    fn phase_and_token_changes_reported_once() {
+        let mut monitor = OutputMonitor::new();
+        let r = monitor.process_chunk("⬡ Thinking...  202 tokens".as_bytes());
+        assert_eq!(r.phase_changed.as_deref(), Some("Thinking"));
+        assert_eq!(r.tokens_changed, Some(202));
+
+        let r = monitor.process_chunk("⬢ Thinking.  202 tokens".as_bytes());
+        assert_eq!(r.phase_changed, None);
+        assert_eq!(r.tokens_changed, None);
+
+        let r = monitor.process_chunk("⬡ Generating..  250 tokens".as_bytes());
+        assert_eq!(r.phase_changed.as_deref(), Some("Generating"));
+        assert_eq!(r.tokens_changed, Some(250));
+    }
+
+    // -- Error screens --
+
+    /// The patterns suggested in `config.toml.example`.
+    const EXAMPLE_PATTERNS: [&str; 3] = [
+        r"(?i)^[\s│]*error: (?:rate limit|network|connection)",
+        r"(?i)^[\s│]*network error:",
+        r"(?i)^[\s│]*authentication expired\b",
+    ];
+
+    fn example_patterns() -> Vec<regex::Regex> {
+        EXAMPLE_PATTERNS.iter().map(|p| regex::Regex::new(p).unwrap()).collect()
+    }
+
+    #[test]
+    fn example_error_patterns_match_banners() {
+        let patterns = example_patterns();
+        for banner in [
+            " │ Error: rate limit exceeded, try again later │",
+            "  Network error: connection reset by peer",
+            "  Authentication expired. Please run `cursor-agent login`",
+        ] {
+            assert!(find_error_line(&patterns, banner).is_some(), "{banner}");
+        }
+        assert_eq!(
+            find_error_line(&patterns, " │ Error: rate limit exceeded │"),
+            Some("Error: rate limit exceeded")
+        );
+    }
+
+    #[test]
+    fn answers_are_not_errors() {
+        let patterns = example_patterns();
+        for line in [
+            "  The API returns 401 Unauthorized when the token expired.",
+            "  Handle the rate limit error: retry after the Retry-After header.",
+        ] {
+            assert_eq!(find_error_line(&patterns, line), None, "{line}");
+        }
+        assert!(crate::config::Errors::default().patterns.is_empty());
+    }
+
+    #[test]
+    fn shots_are_not_errors() {
+        let patterns = example_patterns();
+        for raw in [
+            &include_bytes!("../shots/done/1.txt")[..],
+            &include_bytes!("../shots/thinking/1.txt")[..],
+            &include_bytes!("../shots/generating/1.txt")[..],
+        ] {
+            let text = strip_ansi_escapes::strip(raw);
+            assert_eq!(find_error_line(&patterns, &String::from_utf8_lossy(&text)), None);
+        }
+    }
+
+    #[test]
+    fn error_during_turn_reported_at_transition() {
+        let mut monitor = OutputMonitor::new();
+        monitor.set_error_patterns(&EXAMPLE_PATTERNS.map(String::from));
+        assert!(monitor.process_chunk("⬡ Thinking...".as_bytes()).entered_busy);
+        monitor.process_chunk(b"  Error: rate limit exceeded");
+        monitor.last_busy_seen -= DEBOUNCE * 2;
+        assert_eq!(
+            monitor.check_transition(),
+            Some(TurnEnd::Error("Error: rate limit exceeded".to_string()))
+        );
+        assert_eq!(monitor.check_transition(), None);
+
+        assert!(monitor.process_chunk("⬡ Thinking...".as_bytes()).entered_busy);
+        monitor.last_busy_seen -= DEBOUNCE * 2;
+        assert_eq!(monitor.check_transition(), Some(TurnEnd::Done));
+    }
+
+    // -- Stall watchdog --
+
+    #[test]
+    fn spinner_animation_is_not_progress() {
+        let frames = ["⬡ Thinking.  202 tokens", "⬢ Thinking..  202 tokens", "⬡ Thinking...  202 tokens"];
+        let fps: Vec<_> = frames.iter().map(|f| screen_fingerprint(f)).collect();
+        assert!(fps.iter().all(|fp| *fp == fps[0]));
+        assert_ne!(screen_fingerprint("⬡ Thinking...  203 tokens"), fps[0]);
+        assert_eq!(screen_fingerprint(" ⡕⡰ \r\n "), None);
+    }
+
+    #[test]
+    fn stall_tracks_new_output_only() {
+        let mut monitor = OutputMonitor::new();
+        assert_eq!(monitor.stalled_for(), None);
+
+        monitor.process_chunk("⬡ Thinking.  202 tokens".as_bytes());
+        monitor.last_progress -= Duration::from_secs(60);
+        monitor.process_chunk("⬢ Thinking...  202 tokens".as_bytes());
+        assert!(monitor.stalled_for().unwrap() >= Duration::from_secs(60));
+
+        monitor.process_chunk("⬢ Thinking...  230 tokens".as_bytes());
+        assert!(monitor.stalled_for().unwrap() < Duration::from_secs(60));
+    }
+
+    // -- Agent mode --
+
+    #[test]
+    fn agent_mode_from_done_shot() {
+        let done = strip_ansi_escapes::strip(include_bytes!("../shots/done/1.txt"));
+        // The answer text mentions "(Ask mode)", which must not confuse it.
+        assert_eq!(parse_agent_mode(&String::from_utf8_lossy(&done)), Some(AgentMode::Ask));
+    }
+
+    #[test]
+    fn agent_mode_change_reported_once() {
+        let mut monitor = OutputMonitor::new();
+        let ask = "  ◎ Ask (shift+tab to cycle)".as_bytes();
+        let agent = "  ∞ Agent (shift+tab to cycle)".as_bytes();
+        assert_eq!(monitor.process_chunk(ask).agent_mode_changed, Some(AgentMode::Ask));
+        assert_eq!(monitor.process_chunk(ask).agent_mode_changed, None);
+        assert_eq!(monitor.process_chunk(agent).agent_mode_changed, Some(AgentMode::Agent));
+        assert_eq!(monitor.process_chunk(b"Plan the work").agent_mode_changed, None);
+    }
+
+    // -- Footer: context-window usage --
+
+    #[test]
+    fn context_from_shots() {
+        let done = strip_ansi_escapes::strip(include_bytes!("../shots/done/1.txt"));
+        assert_eq!(parse_context(&String::from_utf8_lossy(&done)), Some(8.3));
+        let thinking = strip_ansi_escapes::strip(include_bytes!("../shots/thinking/1.txt"));
+        assert_eq!(parse_context(&String::from_utf8_lossy(&thinking)), Some(8.0));
+    }
+
+    #[test]
+    fn context_change_reported_once() {
+        let mut monitor = OutputMonitor::new();
+        let mut context = |text: &str| monitor.process_chunk(footer(text).as_bytes()).context_changed;
+        assert_eq!(context("GPT-5 · 8%"), Some(8.0));
+        assert_eq!(context("GPT-5 · 8%"), None);
+        assert_eq!(context("GPT-5 · 9.5%"), Some(9.5));
+        assert_eq!(context("no footer here"), None);
+    }
+
+    #[test]
+    fn model_change_reported_once() {
+        let mut monitor = OutputMonitor::new();
+        let raw = include_bytes!("../shots/thinking/1.txt");
+        assert_eq!(
+            monitor.process_chunk(raw).model_changed.as_deref(),
+            Some("Claude 4.6 Opus (Thinking)")
+        );
+        assert_eq!(monitor.process_chunk(raw).model_changed, None);
+        assert_eq!(
+            monitor.process_chunk(footer("GPT-5 · 1%").as_bytes()).model_changed.as_deref(),
+            Some("GPT-5")
+        );
+    }
 }
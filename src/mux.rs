@@ -0,0 +1,245 @@
+use crate::tmux;
+use std::process::{Command, Stdio};
+use std::sync::{Condvar, LazyLock, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+/// Terminal multiplexer the wrapper runs inside, detected from the
+/// environment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mux {
+    Tmux,
+    Zellij,
+}
+
+static CURRENT: LazyLock<Option<Mux>> = LazyLock::new(|| detect(|name| std::env::var(name).ok()));
+
+/// Pick the multiplexer from its environment variables, preferring tmux
+/// when both are set.
+fn detect(env: impl Fn(&str) -> Option<String>) -> Option<Mux> {
+    let set = |name| env(name).is_some_and(|v| !v.is_empty());
+    if set("TMUX_PANE") || set("TMUX") {
+        Some(Mux::Tmux)
+    } else if set("ZELLIJ") || set("ZELLIJ_SESSION_NAME") {
+        Some(Mux::Zellij)
+    } else {
+        None
+    }
+}
+
+/// The multiplexer we are running in, or `None` in a plain terminal.
+pub fn current() -> Option<Mux> {
+    *CURRENT
+}
+
+/// Name of the multiplexer session we are running in (`{mux-session}`).
+pub fn session() -> Option<String> {
+    match current()? {
+        Mux::Tmux => {
+            let pane = tmux::pane()?;
+            let name = tmux::output(&["display-message", "-p", "-t", pane, "#S"])?;
+            Some(name.trim().to_string())
+        }
+        Mux::Zellij => std::env::var("ZELLIJ_SESSION_NAME").ok(),
+    }
+}
+
+impl Mux {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Mux::Tmux => "tmux",
+            Mux::Zellij => "zellij",
+        }
+    }
+
+    /// Set a per-pane user option such as `@ai-agent-status`.
+    ///
+    /// tmux stores it as a pane option; zellij has no user options, so it
+    /// is sent to plugins as a pipe message instead (see [`zellij_pipe`]).
+    pub fn set_option(self, name: &str, value: &str) {
+        match self {
+            Mux::Tmux => match tmux::pane() {
+                Some(pane) => tmux::run(&["set-option", "-pq", "-t", pane, name, value]),
+                None => tmux::run(&["set-option", "-wq", name, value]),
+            },
+            Mux::Zellij => zellij_pipe(name, value),
+        }
+    }
+
+    /// Remove a per-pane user option; for zellij an empty value is sent.
+    pub fn unset_option(self, name: &str) {
+        match self {
+            Mux::Tmux => match tmux::pane() {
+                Some(pane) => tmux::run(&["set-option", "-pqu", "-t", pane, name]),
+                None => tmux::run(&["set-option", "-wqu", name]),
+            },
+            Mux::Zellij => zellij_pipe(name, ""),
+        }
+    }
+}
+
+/// Name of the zellij pipe option updates are sent on.
+pub const ZELLIJ_PIPE: &str = "ai-agent";
+
+/// How long one `zellij pipe` may wait for a plugin before it is killed.
+const PIPE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Option updates not yet sent to zellij, and whether a `zellij pipe` is
+/// currently running.
+#[derive(Default)]
+struct Pipe {
+    /// Latest value per option, in the order the options first changed.
+    pending: Vec<(String, String)>,
+    busy: bool,
+    /// Set by [`flush`] when time is up: kill the running `zellij pipe`
+    /// and drop what is left.
+    abort: bool,
+}
+
+static PIPE: LazyLock<(Mutex<Pipe>, Condvar)> = LazyLock::new(Default::default);
+
+/// Whether the pipe worker thread was started.
+static PIPE_WORKER: OnceLock<bool> = OnceLock::new();
+
+/// Queue `<pane id>\t<option>\t<value>` for any zellij plugin listening on
+/// the [`ZELLIJ_PIPE`] pipe, e.g. one drawing a status bar.
+///
+/// `zellij pipe` can block until a plugin consumes the message, so
+/// messages are sent one at a time by a background thread, which kills a
+/// pipe nothing picks up. Updates that arrive meanwhile are coalesced:
+/// only the latest value of each option is sent.
+fn zellij_pipe(name: &str, value: &str) {
+    let started = *PIPE_WORKER.get_or_init(|| {
+        std::thread::Builder::new()
+            .name("zellij-pipe".to_string())
+            .spawn(pipe_worker)
+            .is_ok()
+    });
+    if !started {
+        return;
+    }
+    let (lock, cvar) = &*PIPE;
+    let Ok(mut pipe) = lock.lock() else {
+        return;
+    };
+    coalesce(&mut pipe.pending, name, value);
+    cvar.notify_all();
+}
+
+/// Record `value` as the latest for `name`, replacing an unsent one.
+fn coalesce(pending: &mut Vec<(String, String)>, name: &str, value: &str) {
+    match pending.iter_mut().find(|(n, _)| n == name) {
+        Some((_, v)) => *v = value.to_string(),
+        None => pending.push((name.to_string(), value.to_string())),
+    }
+}
+
+fn pipe_worker() {
+    let pane = std::env::var("ZELLIJ_PANE_ID").unwrap_or_default();
+    let (lock, cvar) = &*PIPE;
+    loop {
+        let (name, value) = {
+            let Ok(mut pipe) = lock.lock() else {
+                return;
+            };
+            pipe.busy = false;
+            cvar.notify_all();
+            while pipe.pending.is_empty() {
+                pipe = match cvar.wait(pipe) {
+                    Ok(pipe) => pipe,
+                    Err(_) => return,
+                };
+            }
+            pipe.busy = true;
+            pipe.pending.remove(0)
+        };
+        let payload = format!("{pane}\t{name}\t{value}");
+        let Ok(mut child) = Command::new("zellij")
+            .args(["pipe", "--name", ZELLIJ_PIPE, "--", &payload])
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+        else {
+            continue;
+        };
+        let deadline = Instant::now() + PIPE_TIMEOUT;
+        loop {
+            if let Ok(Some(_)) = child.try_wait() {
+                break;
+            }
+            let aborted = lock.lock().is_ok_and(|pipe| pipe.abort);
+            if aborted || Instant::now() >= deadline {
+                let _ = child.kill();
+                let _ = child.wait();
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        if let Ok(mut pipe) = lock.lock()
+            && pipe.abort
+        {
+            pipe.pending.clear();
+        }
+    }
+}
+
+/// Wait (up to [`PIPE_TIMEOUT`]) for queued zellij messages to be sent,
+/// then kill any `zellij pipe` still running, e.g. before exiting.
+pub fn flush() {
+    if PIPE_WORKER.get() != Some(&true) {
+        return;
+    }
+    let (lock, cvar) = &*PIPE;
+    let Ok(pipe) = lock.lock() else {
+        return;
+    };
+    let working = |pipe: &mut Pipe| pipe.busy || !pipe.pending.is_empty();
+    let Ok((mut pipe, _)) = cvar.wait_timeout_while(pipe, PIPE_TIMEOUT, working) else {
+        return;
+    };
+    if working(&mut pipe) {
+        pipe.abort = true;
+        let _ = cvar.wait_timeout_while(pipe, Duration::from_millis(500), working);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn env(vars: &'static [(&'static str, &'static str)]) -> impl Fn(&str) -> Option<String> {
+        move |name| {
+            vars.iter()
+                .find(|(k, _)| *k == name)
+                .map(|(_, v)| v.to_string())
+        }
+    }
+
+    #[test]
+    fn detect_from_environment() {
+        assert_eq!(detect(env(&[])), None);
+        assert_eq!(detect(env(&[("TMUX_PANE", "%3")])), Some(Mux::Tmux));
+        assert_eq!(detect(env(&[("ZELLIJ", "0")])), Some(Mux::Zellij));
+        assert_eq!(
+            detect(env(&[("ZELLIJ", "0"), ("TMUX_PANE", "%1")])),
+            Some(Mux::Tmux)
+        );
+        assert_eq!(detect(env(&[("TMUX_PANE", "")])), None);
+        assert_eq!(detect(env(&[("TMUX", "/tmp/tmux-0/default,1,0")])), Some(Mux::Tmux));
+    }
+
+    #[test]
+    fn pipe_updates_keep_only_the_latest_value() {
+        let mut pending = Vec::new();
+        coalesce(&mut pending, "@ai-agent-status", "busy");
+        coalesce(&mut pending, "@ai-agent-turns", "1");
+        coalesce(&mut pending, "@ai-agent-status", "idle");
+        assert_eq!(
+            pending,
+            [
+                ("@ai-agent-status".to_string(), "idle".to_string()),
+                ("@ai-agent-turns".to_string(), "1".to_string()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,366 @@
+use crate::mux::{self, Mux};
+use crate::tmux;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock, RwLock};
+use std::time::SystemTime;
+
+/// Placeholder values computed together for one working directory.
+#[derive(Debug, Clone, Default)]
+pub struct Values {
+    values: HashMap<&'static str, String>,
+}
+
+impl Values {
+    /// Compute every provided placeholder for `dir`. Spawns `git` and
+    /// `tmux`, so call this off the async runtime.
+    pub fn collect(dir: &Path) -> Self {
+        let mut values = HashMap::new();
+        values.insert("cwd", dir.display().to_string());
+        values.insert("hostname", hostname());
+
+        let branch = command_output(dir, "git", &["rev-parse", "--abbrev-ref", "HEAD"]);
+        values.insert("git_branch", branch.unwrap_or_default());
+
+        let repo = command_output(dir, "git", &["rev-parse", "--show-toplevel"])
+            .and_then(|top| {
+                Path::new(&top)
+                    .file_name()
+                    .map(|n| n.to_string_lossy().into_owned())
+            });
+        values.insert("git_repo", repo.unwrap_or_default());
+
+        let commit = command_output(dir, "git", &["rev-parse", "--short", "HEAD"]);
+        values.insert("git_commit", commit.unwrap_or_default());
+
+        let dirty = command_output(dir, "git", &["status", "--porcelain"])
+            .is_some_and(|out| !out.is_empty());
+        values.insert("git_dirty", if dirty { "dirty" } else { "" }.to_string());
+
+        let diffstat = command_output(dir, "git", &["diff", "HEAD", "--shortstat"]);
+        values.insert("git_diffstat", diffstat.unwrap_or_default());
+
+        // Target our own pane so the values are right even when another
+        // pane or window is active.
+        let tmux = match mux::current() {
+            Some(Mux::Tmux) => {
+                let mut tmux_args = vec!["display-message", "-p"];
+                if let Some(pane) = tmux::pane() {
+                    tmux_args.extend(["-t", pane]);
+                }
+                tmux_args.push("#S\t#W\t#P");
+                tmux::output(&tmux_args).unwrap_or_default()
+            }
+            _ => String::new(),
+        };
+        let mut tmux = tmux.trim().split('\t');
+        for name in ["tmux-session", "tmux-window", "tmux-pane"] {
+            values.insert(name, tmux.next().unwrap_or_default().to_string());
+        }
+        let mux_session = match mux::current() {
+            Some(Mux::Tmux) => values["tmux-session"].clone(),
+            _ => mux::session().unwrap_or_default(),
+        };
+        values.insert("mux-session", mux_session);
+
+        Self { values }
+    }
+
+    pub fn get(&self, name: &str) -> Option<String> {
+        self.values.get(name).cloned()
+    }
+}
+
+fn hostname() -> String {
+    std::fs::read_to_string("/proc/sys/kernel/hostname")
+        .map(|h| h.trim().to_string())
+        .or_else(|_| std::env::var("HOSTNAME"))
+        .unwrap_or_default()
+}
+
+/// Run a command in `dir` and return its trimmed stdout on success.
+fn command_output(dir: &Path, program: &str, args: &[&str]) -> Option<String> {
+    Command::new(program)
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .ok()
+        .filter(|o| o.status.success())
+        .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string())
+}
+
+/// What the cached values were computed for. The cache is invalidated when
+/// the agent changes directory, the repository changes (checkout, commit,
+/// staging), or a turn starts or ends, see [`invalidate`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct CacheKey {
+    cwd: PathBuf,
+    git: Option<GitState>,
+    generation: u64,
+}
+
+impl CacheKey {
+    /// Cheap to build: one `readlink` plus a few `stat`s and small reads,
+    /// no subprocesses.
+    fn current() -> Self {
+        let cwd = agent_cwd();
+        let git = git_state(&cwd);
+        let generation = GENERATION.load(Ordering::Relaxed);
+        Self { cwd, git, generation }
+    }
+}
+
+/// Bumped by [`invalidate`].
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// Mark the cached values stale. Edits to the work tree don't show in
+/// [`CacheKey`], so this is called when a turn starts and ends, the points
+/// where `{git_dirty}` and `{git_diffstat}` are expected to be current.
+pub fn invalidate() {
+    GENERATION.fetch_add(1, Ordering::Relaxed);
+}
+
+struct Cached {
+    key: CacheKey,
+    values: Arc<Values>,
+}
+
+static AGENT_PID: OnceLock<u32> = OnceLock::new();
+static CACHE: RwLock<Option<Cached>> = RwLock::new(None);
+
+/// Resolve placeholders against the working directory of this process
+/// (the wrapped agent) instead of the wrapper's own.
+pub fn set_agent_pid(pid: u32) {
+    let _ = AGENT_PID.set(pid);
+}
+
+/// PID of the agent child, once known.
+pub fn agent_pid() -> Option<u32> {
+    AGENT_PID.get().copied()
+}
+
+/// Working directory of the agent child, read from `/proc/<pid>/cwd`.
+/// Falls back to our own working directory.
+pub fn agent_cwd() -> PathBuf {
+    AGENT_PID
+        .get()
+        .and_then(|pid| std::fs::read_link(format!("/proc/{pid}/cwd")).ok())
+        .or_else(|| std::env::current_dir().ok())
+        .unwrap_or_default()
+}
+
+/// State of the git repository containing `dir`, as far as it can be read
+/// without running git.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct GitState {
+    /// Contents of `HEAD`: the checked-out branch, or a commit.
+    head: String,
+    /// Commit the checked-out branch points to, changed by commits.
+    target: Option<String>,
+    /// Modification time of the index, changed by staging and commits.
+    index: Option<SystemTime>,
+}
+
+/// Find the git directory for `dir` by walking up the directory tree
+/// (handles `.git` files of worktrees) and read its [`GitState`].
+fn git_state(dir: &Path) -> Option<GitState> {
+    let dot_git = dir.ancestors().map(|d| d.join(".git")).find(|p| p.exists())?;
+    let git_dir = if dot_git.is_file() {
+        let contents = std::fs::read_to_string(&dot_git).ok()?;
+        let target = contents.trim().strip_prefix("gitdir:")?.trim();
+        dot_git.parent()?.join(target)
+    } else {
+        dot_git
+    };
+    // Branches live in the main repository's directory, also for worktrees
+    let common_dir = std::fs::read_to_string(git_dir.join("commondir"))
+        .map(|c| git_dir.join(c.trim()))
+        .unwrap_or_else(|_| git_dir.clone());
+
+    let head = std::fs::read_to_string(git_dir.join("HEAD")).ok()?;
+    let target = head
+        .trim()
+        .strip_prefix("ref:")
+        .and_then(|name| ref_target(&common_dir, name.trim()));
+    let index = std::fs::metadata(git_dir.join("index"))
+        .and_then(|m| m.modified())
+        .ok();
+    Some(GitState { head, target, index })
+}
+
+/// Commit the ref `name` (e.g. `refs/heads/main`) points to, from its
+/// loose ref file or `packed-refs`.
+fn ref_target(git_dir: &Path, name: &str) -> Option<String> {
+    if let Ok(target) = std::fs::read_to_string(git_dir.join(name)) {
+        return Some(target.trim().to_string());
+    }
+    let packed = std::fs::read_to_string(git_dir.join("packed-refs")).ok()?;
+    packed.lines().find_map(|line| {
+        let (commit, packed_name) = line.split_once(' ')?;
+        (packed_name == name).then(|| commit.to_string())
+    })
+}
+
+fn cached(key: &CacheKey) -> Option<Arc<Values>> {
+    let cache = CACHE.read().ok()?;
+    cache
+        .as_ref()
+        .filter(|c| c.key == *key)
+        .map(|c| Arc::clone(&c.values))
+}
+
+fn store(key: CacheKey, values: Values) {
+    if let Ok(mut cache) = CACHE.write() {
+        *cache = Some(Cached {
+            key,
+            values: Arc::new(values),
+        });
+    }
+}
+
+/// The cached placeholder values, possibly stale, or empty ones before
+/// the first [`refresh`]. Never runs `git` or `tmux`, so it is safe to call
+/// from async code.
+pub fn current() -> Arc<Values> {
+    CACHE
+        .read()
+        .ok()
+        .and_then(|cache| cache.as_ref().map(|c| Arc::clone(&c.values)))
+        .unwrap_or_default()
+}
+
+/// Recompute the cached values on the calling thread if they are stale.
+/// For code outside the async runtime, e.g. the backend.
+pub fn refresh_blocking() {
+    let key = CacheKey::current();
+    if cached(&key).is_none() {
+        let values = Values::collect(&key.cwd);
+        store(key, values);
+    }
+}
+
+/// Recompute the cached values on a blocking thread if they are stale.
+/// Called once per turn, and before sending a notification.
+pub async fn refresh() {
+    let _ = tokio::task::spawn_blocking(refresh_blocking).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A throwaway git repository under the system temp dir.
+    struct FakeRepo {
+        dir: PathBuf,
+    }
+
+    impl FakeRepo {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir()
+                .join(format!("ccw-{}-{name}", std::process::id()))
+                .join(name);
+            let _ = std::fs::remove_dir_all(&dir);
+            std::fs::create_dir_all(&dir).unwrap();
+            let repo = Self { dir };
+            repo.git(&["init", "-q", "-b", "feature/x"]);
+            repo.write("README", "hello\n");
+            repo.git(&["add", "README"]);
+            repo.git(&["commit", "-q", "-m", "init"]);
+            repo
+        }
+
+        fn git(&self, args: &[&str]) -> String {
+            let out = Command::new("git")
+                .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+                .args(["-c", "commit.gpgsign=false"])
+                .args(args)
+                .current_dir(&self.dir)
+                .output()
+                .unwrap();
+            assert!(out.status.success(), "git {args:?} failed");
+            String::from_utf8_lossy(&out.stdout).trim().to_string()
+        }
+
+        fn write(&self, file: &str, contents: &str) {
+            std::fs::write(self.dir.join(file), contents).unwrap();
+        }
+    }
+
+    impl Drop for FakeRepo {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(self.dir.parent().unwrap());
+        }
+    }
+
+    #[test]
+    fn clean_repo_values() {
+        let repo = FakeRepo::new("clean-repo");
+        let values = Values::collect(&repo.dir);
+
+        assert_eq!(values.get("cwd").unwrap(), repo.dir.display().to_string());
+        assert_eq!(values.get("git_branch").unwrap(), "feature/x");
+        assert_eq!(values.get("git_repo").unwrap(), "clean-repo");
+        assert_eq!(
+            values.get("git_commit").unwrap(),
+            repo.git(&["rev-parse", "--short", "HEAD"])
+        );
+        assert_eq!(values.get("git_dirty").unwrap(), "");
+        assert_eq!(values.get("git_diffstat").unwrap(), "");
+        assert!(!values.get("hostname").unwrap().is_empty());
+    }
+
+    #[test]
+    fn dirty_repo_values() {
+        let repo = FakeRepo::new("dirty-repo");
+        repo.write("README", "hello\nworld\n");
+        let values = Values::collect(&repo.dir);
+
+        assert_eq!(values.get("git_dirty").unwrap(), "dirty");
+        assert_eq!(
+            values.get("git_diffstat").unwrap(),
+            "1 file changed, 1 insertion(+)"
+        );
+    }
+
+    #[test]
+    fn subdirectory_resolves_to_repo() {
+        let repo = FakeRepo::new("nested-repo");
+        let sub = repo.dir.join("src");
+        std::fs::create_dir_all(&sub).unwrap();
+        let values = Values::collect(&sub);
+
+        assert_eq!(values.get("git_repo").unwrap(), "nested-repo");
+        assert_eq!(git_state(&sub).unwrap().head.trim(), "ref: refs/heads/feature/x");
+    }
+
+    #[test]
+    fn commits_change_the_git_state() {
+        let repo = FakeRepo::new("state-repo");
+        let before = git_state(&repo.dir).unwrap();
+        assert_eq!(before.target.as_deref(), Some(repo.git(&["rev-parse", "HEAD"]).as_str()));
+
+        repo.write("README", "changed\n");
+        repo.git(&["commit", "-q", "-am", "second"]);
+        let after = git_state(&repo.dir).unwrap();
+        assert_eq!(after.head, before.head);
+        assert_ne!(after.target, before.target);
+
+        repo.git(&["pack-refs", "--all"]);
+        assert_eq!(git_state(&repo.dir).unwrap().target, after.target);
+    }
+
+    #[test]
+    fn outside_git_values_are_empty() {
+        let dir = std::env::temp_dir().join(format!("ccw-{}-no-git", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let values = Values::collect(&dir);
+        let _ = std::fs::remove_dir_all(&dir);
+
+        for name in ["git_branch", "git_repo", "git_commit", "git_dirty", "git_diffstat"] {
+            assert_eq!(values.get(name).unwrap(), "", "{name}");
+        }
+        assert!(values.get("unknown").is_none());
+    }
+}
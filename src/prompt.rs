@@ -0,0 +1,223 @@
+use crate::state::VimMode;
+
+const ESC: u8 = 0x1b;
+const PASTE_START: &[u8] = b"\x1b[200~";
+const PASTE_END: &[u8] = b"\x1b[201~";
+
+/// A line submitted with Enter.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Submitted {
+    Prompt(String),
+    /// The line was edited in ways the tracker can't follow (history
+    /// recall, cursor movement, vim NORMAL mode), so its text is unknown.
+    Unknown,
+}
+
+/// Reconstructs the prompt the user submits from raw stdin bytes, so it can
+/// be re-sent after an error.
+///
+/// This is a best-effort line editor: printable text, backspace, Ctrl+U and
+/// bracketed paste are understood. Any other key that may change the line
+/// (arrows, history, other control keys, keys typed in vim NORMAL mode)
+/// makes the line unknown until Ctrl+U or Enter starts a new one.
+#[derive(Debug)]
+pub struct PromptTracker {
+    /// `None` while the line is unknown.
+    line: Option<String>,
+    in_paste: bool,
+    /// Bytes of an incomplete UTF-8 sequence split across reads.
+    pending: Vec<u8>,
+}
+
+impl Default for PromptTracker {
+    fn default() -> Self {
+        Self {
+            line: Some(String::new()),
+            in_paste: false,
+            pending: Vec::new(),
+        }
+    }
+}
+
+impl PromptTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feed raw input bytes typed while the input field was in `vim_mode`.
+    /// Returns what was submitted when Enter submits a non-empty line.
+    pub fn feed(&mut self, data: &[u8], vim_mode: VimMode) -> Option<Submitted> {
+        let mut submitted = None;
+        let mut i = 0;
+        while i < data.len() {
+            let rest = &data[i..];
+            if rest.starts_with(PASTE_START) {
+                self.in_paste = true;
+                i += PASTE_START.len();
+                continue;
+            }
+            if rest.starts_with(PASTE_END) {
+                self.in_paste = false;
+                i += PASTE_END.len();
+                continue;
+            }
+
+            let normal = vim_mode == VimMode::Normal && !self.in_paste;
+            match rest[0] {
+                ESC => {
+                    let len = escape_len(rest);
+                    if !is_harmless_escape(&rest[..len]) {
+                        self.line = None;
+                    }
+                    i += len;
+                }
+                // Pasted CRLF counts as one newline
+                b'\r' if self.in_paste && rest.get(1) == Some(&b'\n') => i += 1,
+                b'\r' | b'\n' if self.in_paste => {
+                    self.push_text(b"\n");
+                    i += 1;
+                }
+                b'\r' => {
+                    submitted = match self.line.replace(String::new()) {
+                        Some(line) if line.trim().is_empty() => submitted,
+                        Some(line) => Some(Submitted::Prompt(line)),
+                        None => Some(Submitted::Unknown),
+                    };
+                    i += 1;
+                }
+                _ if normal => {
+                    self.line = None;
+                    i += 1;
+                }
+                0x7f | 0x08 => {
+                    if let Some(line) = self.line.as_mut() {
+                        line.pop();
+                    }
+                    i += 1;
+                }
+                // Ctrl+U: clear the line
+                0x15 => {
+                    self.line = Some(String::new());
+                    i += 1;
+                }
+                b if b < 0x20 => {
+                    self.line = None;
+                    i += 1;
+                }
+                _ => {
+                    let len = rest
+                        .iter()
+                        .position(|&b| b < 0x20 || b == 0x7f)
+                        .unwrap_or(rest.len());
+                    self.push_text(&rest[..len]);
+                    i += len;
+                }
+            }
+        }
+        submitted
+    }
+
+    fn push_text(&mut self, bytes: &[u8]) {
+        let Some(line) = self.line.as_mut() else {
+            return;
+        };
+        self.pending.extend_from_slice(bytes);
+        match std::str::from_utf8(&self.pending) {
+            Ok(text) => {
+                line.push_str(text);
+                self.pending.clear();
+            }
+            Err(e) => {
+                let valid = e.valid_up_to();
+                line.push_str(&String::from_utf8_lossy(&self.pending[..valid]));
+                self.pending.drain(..valid);
+                if e.error_len().is_some() {
+                    self.pending.clear();
+                }
+            }
+        }
+    }
+}
+
+/// Escape sequences that don't change the line: ESC itself (entering vim
+/// NORMAL mode), also in its kitty keyboard encoding, and focus reports.
+fn is_harmless_escape(seq: &[u8]) -> bool {
+    matches!(seq, b"\x1b" | b"\x1b[27;1u" | b"\x1b[27u" | b"\x1b[I" | b"\x1b[O")
+}
+
+/// Length of the escape sequence at the start of `data` (which begins with
+/// ESC): CSI (`ESC [ ... final`), SS3 (`ESC O x`), or `ESC x`.
+fn escape_len(data: &[u8]) -> usize {
+    match data.get(1) {
+        Some(b'[') => data[2..]
+            .iter()
+            .position(|b| (0x40..=0x7e).contains(b))
+            .map_or(data.len(), |p| p + 3),
+        Some(b'O') => 3.min(data.len()),
+        Some(_) => 2,
+        None => 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn prompt(text: &str) -> Option<Submitted> {
+        Some(Submitted::Prompt(text.to_string()))
+    }
+
+    #[test]
+    fn typed_prompt_is_submitted_on_enter() {
+        let mut t = PromptTracker::new();
+        assert_eq!(t.feed(b"fix the ", VimMode::Insert), None);
+        assert_eq!(t.feed(b"tests\r", VimMode::Insert), prompt("fix the tests"));
+        assert_eq!(t.feed(b"\r", VimMode::Insert), None);
+    }
+
+    #[test]
+    fn backspace_and_ctrl_u_edit_the_line() {
+        let mut t = PromptTracker::new();
+        assert_eq!(t.feed(b"hellp\x7fo\r", VimMode::Insert), prompt("hello"));
+        assert_eq!(t.feed(b"junk\x15again\r", VimMode::Insert), prompt("again"));
+    }
+
+    #[test]
+    fn harmless_escape_sequences_are_ignored() {
+        let mut t = PromptTracker::new();
+        assert_eq!(t.feed(b"a\x1b[Ib\x1b[27;1uc\r", VimMode::Insert), prompt("abc"));
+    }
+
+    #[test]
+    fn history_and_cursor_keys_make_the_line_unknown() {
+        let mut t = PromptTracker::new();
+        assert_eq!(t.feed(b"\x1b[A\r", VimMode::Insert), Some(Submitted::Unknown));
+        assert_eq!(t.feed(b"ab\x1b[Dc\r", VimMode::Insert), Some(Submitted::Unknown));
+        // Ctrl+U clears the line, so it is known again
+        assert_eq!(t.feed(b"x\x1b[A\x15new\r", VimMode::Insert), prompt("new"));
+    }
+
+    #[test]
+    fn bracketed_paste_keeps_newlines() {
+        let mut t = PromptTracker::new();
+        let submitted = t.feed(b"\x1b[200~line one\r\nline two\x1b[201~\r", VimMode::Insert);
+        assert_eq!(submitted, prompt("line one\nline two"));
+    }
+
+    #[test]
+    fn normal_mode_edits_make_the_line_unknown() {
+        let mut t = PromptTracker::new();
+        t.feed(b"draft", VimMode::Insert);
+        t.feed(b"dd", VimMode::Normal);
+        assert_eq!(t.feed(b"\r", VimMode::Normal), Some(Submitted::Unknown));
+        assert_eq!(t.feed(b"next\r", VimMode::Insert), prompt("next"));
+    }
+
+    #[test]
+    fn utf8_split_across_reads() {
+        let mut t = PromptTracker::new();
+        let bytes = "héllo".as_bytes();
+        t.feed(&bytes[..2], VimMode::Insert);
+        assert_eq!(t.feed(&[&bytes[2..], b"\r"].concat(), VimMode::Insert), prompt("héllo"));
+    }
+}
@@ -0,0 +1,327 @@
+use crate::mux::{self, Mux};
+use crate::{log, placeholders, state, tmux};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Snapshot of one wrapper session, written as JSON to
+/// `<dir>/<pid>.json` so status bars can read it without tmux.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Entry {
+    /// Session label (`{session}`).
+    pub name: String,
+    /// PID of the wrapper.
+    pub pid: u32,
+    /// PID of the wrapped cursor-agent.
+    pub agent_pid: Option<u32>,
+    /// `tmux`, `zellij`, or empty outside a multiplexer.
+    pub mux: String,
+    /// Pane the wrapper runs in (`%3` in tmux, the pane id in zellij).
+    pub pane: String,
+    /// Canonical status name (`WAITING`, ...), empty once cleared.
+    pub status: String,
+    /// Display text of the status, from the `[status]` config.
+    pub status_text: String,
+    /// `normal` or `insert`.
+    pub vim_mode: String,
+    pub model: String,
+    /// Working directory of the agent.
+    pub cwd: String,
+    pub repo: String,
+    pub branch: String,
+    /// Unix timestamps, in seconds.
+    pub started_at: u64,
+    pub status_since: u64,
+    pub updated_at: u64,
+}
+
+/// When this wrapper started; set by [`enable`], which also makes
+/// [`update`] write our entry. Other processes (the backend) never do.
+static STARTED_AT: OnceLock<u64> = OnceLock::new();
+
+fn epoch_secs(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+}
+
+/// `$XDG_RUNTIME_DIR/cursor-cli-wrapper`, or a per-user directory under
+/// the system temp dir when that is unset.
+pub fn dir() -> PathBuf {
+    match std::env::var_os("XDG_RUNTIME_DIR").filter(|d| !d.is_empty()) {
+        Some(runtime) => PathBuf::from(runtime).join("cursor-cli-wrapper"),
+        None => std::env::temp_dir().join(format!("cursor-cli-wrapper-{}", unsafe { libc::getuid() })),
+    }
+}
+
+fn entry_path(dir: &Path, pid: u32) -> PathBuf {
+    dir.join(format!("{pid}.json"))
+}
+
+/// Start publishing this wrapper's entry, and write the first one.
+pub fn enable() {
+    let _ = STARTED_AT.set(epoch_secs(SystemTime::now()));
+    update();
+}
+
+/// Serializes [`update`], which runs on several tasks at once (the stdout
+/// relay, placeholder refreshes) and rewrites the same temp file.
+static UPDATE: Mutex<()> = Mutex::new(());
+
+/// Rewrite our entry from the current session state. Does nothing unless
+/// [`enable`] was called.
+pub fn update() {
+    let Some(&started_at) = STARTED_AT.get() else {
+        return;
+    };
+    // Snapshot the state under the lock too, so the last write is the
+    // newest state
+    let _guard = UPDATE.lock().unwrap_or_else(|e| e.into_inner());
+    let session = state::session();
+    let values = placeholders::current();
+    let (mux, pane) = match mux::current() {
+        Some(Mux::Tmux) => ("tmux", tmux::pane().map(str::to_string)),
+        Some(Mux::Zellij) => ("zellij", std::env::var("ZELLIJ_PANE_ID").ok()),
+        None => ("", None),
+    };
+    let entry = Entry {
+        name: session.name,
+        pid: std::process::id(),
+        agent_pid: placeholders::agent_pid(),
+        mux: mux.to_string(),
+        pane: pane.unwrap_or_default(),
+        status: session.status.map(|s| s.as_str().to_string()).unwrap_or_default(),
+        status_text: session.status_text,
+        vim_mode: state::get_vim_mode().as_str().to_string(),
+        model: session.model,
+        cwd: values.get("cwd").unwrap_or_default(),
+        repo: values.get("git_repo").unwrap_or_default(),
+        branch: values.get("git_branch").unwrap_or_default(),
+        started_at,
+        status_since: session.status_since.map(epoch_secs).unwrap_or(started_at),
+        updated_at: epoch_secs(SystemTime::now()),
+    };
+    if let Err(e) = write(&dir(), &entry) {
+        log::info(format!("registry: cannot write {}: {e}", dir().display()));
+    }
+}
+
+/// Create `dir` private to us, or check that an existing one is: a real
+/// directory (not a symlink), owned by us, with no access for anyone else.
+/// Under a shared temp dir another user could have created it first.
+fn ensure_dir(dir: &Path) -> std::io::Result<()> {
+    use std::os::unix::fs::DirBuilderExt;
+    match std::fs::DirBuilder::new().mode(0o700).create(dir) {
+        Err(e) if e.kind() != std::io::ErrorKind::AlreadyExists => return Err(e),
+        _ => {}
+    }
+    check_dir(dir)
+}
+
+fn check_dir(dir: &Path) -> std::io::Result<()> {
+    use std::os::unix::fs::MetadataExt;
+    let meta = std::fs::symlink_metadata(dir)?;
+    if !meta.is_dir() || meta.uid() != unsafe { libc::getuid() } || meta.mode() & 0o077 != 0 {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::PermissionDenied,
+            "not a directory private to this user",
+        ));
+    }
+    Ok(())
+}
+
+/// Write `entry` atomically: readers see either the old or the new file,
+/// never a partial one.
+fn write(dir: &Path, entry: &Entry) -> std::io::Result<()> {
+    ensure_dir(dir)?;
+    let path = entry_path(dir, entry.pid);
+    let tmp = path.with_extension("json.tmp");
+    let mut file = std::fs::File::create(&tmp)?;
+    file.write_all(&serde_json::to_vec(entry)?)?;
+    std::fs::rename(&tmp, &path)
+}
+
+/// Remove our entry; called on exit.
+pub fn remove() {
+    if STARTED_AT.get().is_some() {
+        let _ = std::fs::remove_file(entry_path(&dir(), std::process::id()));
+    }
+}
+
+fn is_alive(pid: u32) -> bool {
+    let Ok(pid) = libc::pid_t::try_from(pid) else {
+        return false;
+    };
+    let alive = unsafe { libc::kill(pid, 0) } == 0;
+    alive || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+/// Entries of all running wrappers, oldest first. Files left behind by
+/// wrappers that died without cleaning up are deleted.
+pub fn entries() -> Vec<Entry> {
+    entries_in(&dir(), is_alive)
+}
+
+fn entries_in(dir: &Path, alive: impl Fn(u32) -> bool) -> Vec<Entry> {
+    if check_dir(dir).is_err() {
+        return Vec::new();
+    }
+    let Ok(files) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut entries = Vec::new();
+    for path in files.flatten().map(|f| f.path()) {
+        let Some(pid) = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .and_then(|n| n.strip_suffix(".json"))
+            .and_then(|pid| pid.parse::<u32>().ok())
+        else {
+            continue;
+        };
+        if !alive(pid) {
+            let _ = std::fs::remove_file(&path);
+            continue;
+        }
+        let entry = std::fs::read(&path)
+            .ok()
+            .and_then(|data| serde_json::from_slice::<Entry>(&data).ok());
+        if let Some(entry) = entry {
+            entries.push(entry);
+        }
+    }
+    entries.sort_by_key(|e| (e.started_at, e.pid));
+    entries
+}
+
+/// Human-readable table of `entries`, one row per session, with ages
+/// relative to `now` (Unix seconds).
+pub fn table(entries: &[Entry], now: u64) -> String {
+    let ago = |t: u64| state::format_duration(std::time::Duration::from_secs(now.saturating_sub(t)));
+    let mut rows = vec![[
+        "NAME", "PID", "AGENT", "STATUS", "SINCE", "REPO", "BRANCH", "PANE", "STARTED", "CWD",
+    ]
+    .map(String::from)];
+    for e in entries {
+        rows.push([
+            e.name.clone(),
+            e.pid.to_string(),
+            e.agent_pid.map(|p| p.to_string()).unwrap_or_default(),
+            e.status_text.clone(),
+            ago(e.status_since),
+            e.repo.clone(),
+            e.branch.clone(),
+            e.pane.clone(),
+            ago(e.started_at),
+            e.cwd.clone(),
+        ]);
+    }
+
+    let mut widths = [0; 10];
+    for row in &rows {
+        for (w, cell) in widths.iter_mut().zip(row) {
+            *w = (*w).max(cell.chars().count());
+        }
+    }
+    let mut out = String::new();
+    for row in &rows {
+        let cells: Vec<String> = row
+            .iter()
+            .zip(widths)
+            .map(|(cell, w)| format!("{cell:<w$}"))
+            .collect();
+        out.push_str(cells.join("  ").trim_end());
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entries_round_trip_and_prune_dead_pids() {
+        let dir = std::env::temp_dir().join(format!("ccw-{}-registry", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let live = Entry {
+            pid: 100,
+            status: "WAITING".to_string(),
+            repo: "crate".to_string(),
+            started_at: 2,
+            ..Default::default()
+        };
+        let older = Entry {
+            pid: 101,
+            started_at: 1,
+            ..Default::default()
+        };
+        let dead = Entry {
+            pid: 102,
+            ..Default::default()
+        };
+        for entry in [&live, &older, &dead] {
+            write(&dir, entry).unwrap();
+        }
+        std::fs::write(dir.join("notes.txt"), "ignored").unwrap();
+
+        let entries = entries_in(&dir, |pid| pid != 102);
+        assert_eq!(entries, vec![older, live]);
+        assert!(!entry_path(&dir, 102).exists());
+        assert!(!entry_path(&dir, 100).with_extension("json.tmp").exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn shared_directories_are_refused() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = std::env::temp_dir().join(format!("ccw-{}-registry-shared", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir(&dir).unwrap();
+        std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o777)).unwrap();
+        let entry = Entry {
+            pid: 100,
+            ..Default::default()
+        };
+        assert!(write(&dir, &entry).is_err());
+        assert!(!entry_path(&dir, 100).exists());
+
+        let link = dir.with_extension("link");
+        let _ = std::fs::remove_file(&link);
+        std::os::unix::fs::symlink(&dir, &link).unwrap();
+        std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o700)).unwrap();
+        assert!(write(&link, &entry).is_err());
+        assert!(write(&dir, &entry).is_ok());
+        let _ = std::fs::remove_file(&link);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn table_aligns_columns() {
+        let entry = Entry {
+            name: "app".to_string(),
+            pid: 4242,
+            agent_pid: Some(4243),
+            status_text: "WAITING".to_string(),
+            status_since: 940,
+            repo: "app".to_string(),
+            branch: "main".to_string(),
+            pane: "%3".to_string(),
+            cwd: "/src/app".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(
+            table(&[entry], 1000),
+            "NAME  PID   AGENT  STATUS   SINCE   REPO  BRANCH  PANE  STARTED  CWD\n\
+             app   4242  4243   WAITING  1m 00s  app   main    %3    16m 40s  /src/app\n"
+        );
+    }
+
+    #[test]
+    fn old_files_without_new_fields_still_parse() {
+        let entry: Entry = serde_json::from_str(r#"{"pid":7,"status":"IDLE"}"#).unwrap();
+        assert_eq!(entry.pid, 7);
+        assert_eq!(entry.agent_pid, None);
+    }
+}
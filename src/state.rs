@@ -1,4 +1,8 @@
+use crate::{config, mux, placeholders, registry, term, tmux};
+use std::collections::VecDeque;
 use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::RwLock;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 /// The current vim mode of the Cursor Agent input field.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -38,47 +42,521 @@ pub fn get_vim_mode() -> VimMode {
     VimMode::from_u8(VIM_MODE.load(Ordering::Relaxed))
 }
 
-/// Run a shell command in the foreground, discarding output.
-pub fn run_hook(cmd: &str) {
+/// The mode the Cursor Agent is in, cycled with shift+tab.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum AgentMode {
+    Ask = 1,
+    Agent = 2,
+    Plan = 3,
+}
+
+impl AgentMode {
+    fn from_u8(v: u8) -> Option<Self> {
+        match v {
+            1 => Some(AgentMode::Ask),
+            2 => Some(AgentMode::Agent),
+            3 => Some(AgentMode::Plan),
+            _ => None,
+        }
+    }
+
+    /// Parse the mode name as shown by the agent (e.g. `Ask`).
+    pub fn from_label(label: &str) -> Option<Self> {
+        match label {
+            "Ask" => Some(AgentMode::Ask),
+            "Agent" => Some(AgentMode::Agent),
+            "Plan" => Some(AgentMode::Plan),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AgentMode::Ask => "ask",
+            AgentMode::Agent => "agent",
+            AgentMode::Plan => "plan",
+        }
+    }
+}
+
+/// Global atomic storing the current agent mode (0 = not seen yet).
+static AGENT_MODE: AtomicU8 = AtomicU8::new(0);
+
+/// Update the tracked agent mode.
+pub fn set_agent_mode(mode: AgentMode) {
+    AGENT_MODE.store(mode as u8, Ordering::Relaxed);
+}
+
+/// Read the current agent mode, if it has been seen yet.
+pub fn get_agent_mode() -> Option<AgentMode> {
+    AgentMode::from_u8(AGENT_MODE.load(Ordering::Relaxed))
+}
+
+/// Status of the wrapped agent, as published to tmux and hooks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Status {
+    /// Waiting for the first prompt, or reset with Alt+I.
+    Idle,
+    /// Busy with a prompt.
+    InProgress,
+    /// Finished a turn; waiting for the user.
+    Waiting,
+    /// A turn ended with an error screen.
+    Error,
+    /// Busy, but no progress for longer than the watchdog allows.
+    Stalled,
+    /// Busy sub-status: thinking.
+    Thinking,
+    /// Busy sub-status: generating the answer.
+    Generating,
+    /// Busy sub-status: anything else (reading files, running commands, ...).
+    Tool,
+}
+
+impl Status {
+    pub const ALL: [Status; 8] = [
+        Status::Idle,
+        Status::InProgress,
+        Status::Waiting,
+        Status::Error,
+        Status::Stalled,
+        Status::Thinking,
+        Status::Generating,
+        Status::Tool,
+    ];
+
+    /// Canonical upper-case name, used when no display text is configured.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Status::Idle => "IDLE",
+            Status::InProgress => "INPROGRESS",
+            Status::Waiting => "WAITING",
+            Status::Error => "ERROR",
+            Status::Stalled => "STALLED",
+            Status::Thinking => "THINKING",
+            Status::Generating => "GENERATING",
+            Status::Tool => "TOOL",
+        }
+    }
+
+    /// Parse a canonical name, case-insensitively.
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|s| s.as_str().eq_ignore_ascii_case(name))
+    }
+
+    /// Busy sub-status for the phase name shown next to the spinner.
+    pub fn from_phase(phase: &str) -> Self {
+        match phase {
+            "Thinking" => Status::Thinking,
+            "Generating" => Status::Generating,
+            _ => Status::Tool,
+        }
+    }
+
+    /// How much the status needs the user's attention; the most urgent
+    /// status of the panes in a window is shown for the whole window.
+    pub fn urgency(self) -> u8 {
+        match self {
+            Status::Idle => 0,
+            Status::InProgress | Status::Thinking | Status::Generating | Status::Tool => 1,
+            Status::Waiting => 2,
+            Status::Stalled => 3,
+            Status::Error => 4,
+        }
+    }
+}
+
+/// Details about this wrapper session and its current turn, exposed as
+/// placeholders.
+#[derive(Debug, Clone, Default)]
+pub struct Session {
+    /// Label for this session (`{session}`).
+    pub name: String,
+    /// Name of the config profile that was applied (`{profile}`).
+    pub profile: Option<String>,
+    /// Last status passed to [`set_status`].
+    pub status: Option<Status>,
+    /// Display text of `status`, from the `[status]` config.
+    pub status_text: String,
+    /// When `status` was last changed.
+    pub status_since: Option<SystemTime>,
+    /// Number of turns (prompts the agent has worked on) so far.
+    pub turn: u32,
+    pub turn_started: Option<Instant>,
+    /// `None` while the current turn is still running.
+    pub turn_finished: Option<Instant>,
+    /// Active model name, as shown in the agent footer.
+    pub model: String,
+    /// Context-window usage in percent, as shown in the agent footer.
+    pub context: Option<f32>,
+    /// Busy phase shown next to the spinner (e.g. `Thinking`); `None` when idle.
+    pub phase: Option<String>,
+    /// Token count of the current turn, or the final count of the last turn.
+    pub tokens: Option<u64>,
+    /// Error line that ended the last turn, if it ended in an error.
+    pub error: Option<String>,
+    /// Last prompt submitted by the user, for re-sending after an error.
+    pub last_prompt: Option<String>,
+    /// Statistics of the last [`TURN_HISTORY`] finished turns, oldest
+    /// first.
+    pub history: VecDeque<TurnRecord>,
+}
+
+/// Number of finished turns kept in [`Session::history`].
+pub const TURN_HISTORY: usize = 100;
+
+/// Statistics recorded when a turn finishes.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TurnRecord {
+    pub number: u32,
+    pub duration: Duration,
+    /// Model shown in the footer while the turn ran.
+    pub model: String,
+    /// Last token count shown while the turn ran.
+    pub tokens: Option<u64>,
+}
+
+impl Session {
+    /// Mark the current turn finished at `now` and record it in the
+    /// history, dropping the oldest record beyond [`TURN_HISTORY`].
+    fn finish_turn(&mut self, now: Instant) {
+        self.turn_finished = Some(now);
+        self.phase = None;
+        if self.history.len() == TURN_HISTORY {
+            self.history.pop_front();
+        }
+        self.history.push_back(TurnRecord {
+            number: self.turn,
+            duration: self.turn_duration().unwrap_or_default(),
+            model: self.model.clone(),
+            tokens: self.tokens,
+        });
+    }
+
+    /// How long the current turn has been running, or how long the last
+    /// turn took once it has finished.
+    pub fn turn_duration(&self) -> Option<Duration> {
+        let started = self.turn_started?;
+        Some(match self.turn_finished {
+            Some(finished) => finished.duration_since(started),
+            None => started.elapsed(),
+        })
+    }
+}
+
+static SESSION: RwLock<Session> = RwLock::new(Session {
+    name: String::new(),
+    profile: None,
+    status: None,
+    status_text: String::new(),
+    status_since: None,
+    turn: 0,
+    turn_started: None,
+    turn_finished: None,
+    model: String::new(),
+    context: None,
+    phase: None,
+    tokens: None,
+    error: None,
+    last_prompt: None,
+    history: VecDeque::new(),
+});
+
+/// Snapshot of the session state.
+pub fn session() -> Session {
+    SESSION.read().map(|s| s.clone()).unwrap_or_default()
+}
+
+/// Modify the session state in place.
+pub fn update_session(f: impl FnOnce(&mut Session)) {
+    if let Ok(mut s) = SESSION.write() {
+        f(&mut s);
+    }
+}
+
+/// Record the start of a new turn.
+pub fn begin_turn() {
+    placeholders::invalidate();
+    update_session(|s| {
+        s.turn += 1;
+        s.turn_started = Some(Instant::now());
+        s.turn_finished = None;
+        s.tokens = None;
+        s.error = None;
+    });
+}
+
+/// Record that the current turn has finished and add it to the history.
+pub fn end_turn() {
+    placeholders::invalidate();
+    update_session(|s| s.finish_turn(Instant::now()));
+}
+
+/// Format a duration compactly, e.g. `42s`, `3m 07s` or `1h 04m`.
+pub fn format_duration(d: Duration) -> String {
+    let secs = d.as_secs();
+    if secs < 60 {
+        format!("{secs}s")
+    } else if secs < 3600 {
+        format!("{}m {:02}s", secs / 60, secs % 60)
+    } else {
+        format!("{}h {:02}m", secs / 3600, (secs % 3600) / 60)
+    }
+}
+
+/// Format a count with thousands separators, e.g. `4,210`.
+pub fn format_count(n: u64) -> String {
+    let digits = n.to_string();
+    let mut out = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            out.push(',');
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// Render the hook command `template` (see [`config::resolve_hook`]) with
+/// the extra values, and run it in the foreground, discarding output.
+pub fn run_hook(template: &str, extra: &[(&str, &str)]) {
+    let cmd = config::resolve_hook(template, extra);
+    if cmd.is_empty() {
+        return;
+    }
     let _ = std::process::Command::new("sh")
-        .args(["-c", cmd])
+        .args(["-c", &cmd])
         .stdout(std::process::Stdio::null())
         .stderr(std::process::Stdio::null())
         .status();
 }
 
-/// Set the tmux user options `@ai-agent-status` and `@ai-agent` on the current
-/// window, and run the `[hooks] status-change` command if configured.
+/// Options also published as terminal user variables (without the `@`)
+/// when there is no multiplexer, for terminal tab bars.
+const USER_VARS: &[&str] = &["@ai-agent-status", "@ai-agent-vim-mode", "@ai-agent-model"];
+
+/// Set a user option on this wrapper's pane in the terminal multiplexer,
+/// so several agents split in one window don't overwrite each other.
+///
+/// Outside tmux and zellij, options in [`USER_VARS`] become terminal user
+/// variables instead.
+pub fn set_mux_option(name: &str, value: &str) {
+    match mux::current() {
+        Some(mux) => mux.set_option(name, value),
+        None if USER_VARS.contains(&name) => term::set_user_var(&name[1..], value),
+        None => {}
+    }
+}
+
+/// Unset a user option on this wrapper's pane so it doesn't linger.
+pub fn unset_mux_option(name: &str) {
+    match mux::current() {
+        Some(mux) => mux.unset_option(name),
+        None if USER_VARS.contains(&name) => term::set_user_var(&name[1..], ""),
+        None => {}
+    }
+}
+
+/// Record the applied config profile and publish it as
+/// `@ai-agent-profile`, unsetting the option when there is none. Called at
+/// startup and on every config reload, which may change the profile.
+pub fn set_profile(profile: Option<String>) {
+    if session().profile == profile {
+        return;
+    }
+    match profile {
+        Some(ref name) => set_mux_option("@ai-agent-profile", name),
+        None => unset_mux_option("@ai-agent-profile"),
+    }
+    update_session(|s| s.profile = profile);
+}
+
+/// The most urgent of `statuses`, or `None` if there are none.
+pub fn aggregate_status(statuses: impl IntoIterator<Item = Status>) -> Option<Status> {
+    statuses.into_iter().max_by_key(|s| s.urgency())
+}
+
+/// Set `@ai-agent-window-status` on this pane's window to the most urgent
+/// status of all agent panes in it, or unset it when none are left.
 ///
-/// Silently does nothing for tmux if not running inside tmux.
-pub fn set_tmux_status(value: &str, hook: Option<&str>) {
-    if value.is_empty() {
-        // Unset the options so they don't linger
-        let _ = std::process::Command::new("tmux")
-            .args(["set-option", "-wqu", "@ai-agent-status"])
-            .stdout(std::process::Stdio::null())
-            .stderr(std::process::Stdio::null())
-            .status();
-        let _ = std::process::Command::new("tmux")
-            .args(["set-option", "-wqu", "@ai-agent"])
-            .stdout(std::process::Stdio::null())
-            .stderr(std::process::Stdio::null())
-            .status();
+/// Runs on the tmux worker thread, after the pane options queued before it.
+fn update_window_status(cfg: &config::Config) {
+    let Some(pane) = tmux::pane() else {
+        return;
+    };
+    let styles = cfg.status.clone();
+    tmux::queue(move |tmux| {
+        let Some(output) = tmux.output(&["list-panes", "-t", pane, "-F", "#{@ai-agent-status}"]) else {
+            return;
+        };
+        // Pane options hold display text; map it back through our own
+        // `[status]` config, then the canonical names.
+        let statuses = output.lines().filter(|l| !l.is_empty()).filter_map(|text| {
+            Status::ALL
+                .into_iter()
+                .find(|s| styles.text(*s) == text)
+                .or_else(|| Status::parse(text))
+        });
+        match aggregate_status(statuses) {
+            Some(status) => tmux.run(&[
+                "set-option",
+                "-wq",
+                "-t",
+                pane,
+                "@ai-agent-window-status",
+                &styles.text(status),
+            ]),
+            None => tmux.run(&["set-option", "-wqu", "-t", pane, "@ai-agent-window-status"]),
+        }
+    });
+}
+
+/// Every pane option the wrapper may set, removed by
+/// [`clear_mux_options`] on exit.
+pub const MUX_OPTIONS: &[&str] = &[
+    "@ai-agent",
+    "@ai-agent-status",
+    "@ai-agent-status-icon",
+    "@ai-agent-status-colour",
+    "@ai-agent-since",
+    "@ai-agent-turns",
+    "@ai-agent-session",
+    "@ai-agent-profile",
+    "@ai-agent-vim-mode",
+    "@ai-agent-mode",
+    "@ai-agent-model",
+    "@ai-agent-context",
+    "@ai-agent-phase",
+    "@ai-agent-substatus",
+    "@ai-agent-tokens",
+];
+
+/// Unset every option in [`MUX_OPTIONS`] on this pane, leaving other
+/// panes of the window alone.
+pub fn clear_mux_options() {
+    for name in MUX_OPTIONS {
+        unset_mux_option(name);
+    }
+}
+
+/// Publish `status` (or clear it with `None`): set the pane options
+/// `@ai-agent`, `@ai-agent-status`, `@ai-agent-since` (epoch seconds the
+/// status started), `@ai-agent-turns` and, when configured,
+/// `@ai-agent-status-icon` / `@ai-agent-status-colour`, refresh the tmux
+/// window's aggregated `@ai-agent-window-status`, set the terminal title
+/// from `[general] window-title` (restored when clearing), rewrite the
+/// registry entry, and run the `[hooks] status-change` command if
+/// configured.
+pub fn set_status(status: Option<Status>, cfg: &config::Config) {
+    let style = status.map(|s| cfg.status.style(s)).unwrap_or_default();
+    let text = status.map(|s| cfg.status.text(s)).unwrap_or_default();
+    let now = SystemTime::now();
+    update_session(|s| {
+        s.status = status;
+        s.status_text = text.clone();
+        s.status_since = Some(now);
+    });
+
+    if status.is_none() {
+        for name in ["@ai-agent-status", "@ai-agent", "@ai-agent-since", "@ai-agent-turns"] {
+            unset_mux_option(name);
+        }
     } else {
-        let _ = std::process::Command::new("tmux")
-            .args(["set-option", "-wq", "@ai-agent", "cursor"])
-            .stdout(std::process::Stdio::null())
-            .stderr(std::process::Stdio::null())
-            .status();
-        let _ = std::process::Command::new("tmux")
-            .args(["set-option", "-wq", "@ai-agent-status", value])
-            .stdout(std::process::Stdio::null())
-            .stderr(std::process::Stdio::null())
-            .status();
-    }
-
-    if let Some(cmd) = hook {
-        let cmd = cmd.replace("{status}", value);
-        run_hook(&cmd);
+        let since = now.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        set_mux_option("@ai-agent", "cursor");
+        set_mux_option("@ai-agent-status", &text);
+        set_mux_option("@ai-agent-since", &since.to_string());
+        set_mux_option("@ai-agent-turns", &session().turn.to_string());
+    }
+    for (option, value) in [
+        ("@ai-agent-status-icon", &style.icon),
+        ("@ai-agent-status-colour", &style.colour),
+    ] {
+        match value {
+            Some(value) => set_mux_option(option, value),
+            None => unset_mux_option(option),
+        }
+    }
+    update_window_status(cfg);
+
+    let title = &cfg.general.window_title;
+    if status.is_none() || title.is_empty() {
+        term::restore_title();
+    } else {
+        term::set_title(&config::resolve_placeholders(title));
+    }
+    registry::update();
+
+    if let Some(ref cmd) = cfg.hooks.status_change {
+        // Scripts match on the canonical name, whatever the display text
+        let name = status.map_or("", Status::as_str);
+        run_hook(cmd, &[("status", name), ("status_text", &text)]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn turns_record_the_model_they_ran_with() {
+        let mut session = Session::default();
+        let start = Instant::now();
+        for (model, tokens) in [("GPT-5", 120), ("GPT-5", 80), ("Claude 4.6 Opus", 300)] {
+            // The footer shows a newly picked model before the next turn
+            session.model = model.to_string();
+            session.turn += 1;
+            session.turn_started = Some(start);
+            session.tokens = Some(tokens);
+            session.finish_turn(start + Duration::from_secs(tokens));
+        }
+        let turns: Vec<_> = session
+            .history
+            .iter()
+            .map(|t| (t.number, t.model.as_str(), t.tokens, t.duration.as_secs()))
+            .collect();
+        assert_eq!(
+            turns,
+            [
+                (1, "GPT-5", Some(120), 120),
+                (2, "GPT-5", Some(80), 80),
+                (3, "Claude 4.6 Opus", Some(300), 300),
+            ]
+        );
+
+        for _ in 0..TURN_HISTORY {
+            session.turn += 1;
+            session.finish_turn(start);
+        }
+        assert_eq!(session.history.len(), TURN_HISTORY);
+        assert_eq!(session.history[0].number, 4);
+    }
+
+    #[test]
+    fn format_duration_units() {
+        assert_eq!(format_duration(Duration::from_secs(0)), "0s");
+        assert_eq!(format_duration(Duration::from_secs(42)), "42s");
+        assert_eq!(format_duration(Duration::from_secs(187)), "3m 07s");
+        assert_eq!(format_duration(Duration::from_secs(3840)), "1h 04m");
+    }
+
+    #[test]
+    fn aggregate_status_prefers_attention() {
+        use Status::*;
+        assert_eq!(aggregate_status([]), None);
+        assert_eq!(aggregate_status([Idle, Thinking]), Some(Thinking));
+        assert_eq!(aggregate_status([InProgress, Waiting, Idle]), Some(Waiting));
+        assert_eq!(aggregate_status([Waiting, Error, Stalled]), Some(Error));
+    }
+
+    #[test]
+    fn format_count_separators() {
+        assert_eq!(format_count(0), "0");
+        assert_eq!(format_count(202), "202");
+        assert_eq!(format_count(4210), "4,210");
+        assert_eq!(format_count(1234567), "1,234,567");
     }
 }
@@ -0,0 +1,527 @@
+//! Mini template language used by notification titles/bodies and hook
+//! commands.
+//!
+//! Syntax:
+//! - `{name}` — value of a placeholder.
+//! - `{name|filter|filter:arg}` — value passed through filters:
+//!   `default:"x"` (used when the value is empty), `truncate:N`, `lower`,
+//!   `upper`.
+//! - `{?name}...{/}` — section rendered only when `name` is non-empty.
+//!   Sections may be nested.
+//! - `{{` and `}}` — literal braces. A `}}` only stands for `}` when it
+//!   closes an earlier `{{`; otherwise it is kept as two braces, so
+//!   `jq '{a:{b:1}}'` works unchanged. [`Template::unmatched_braces`] lists
+//!   those for `--check-config`.
+//!
+//! `${...}` and any `{` that does not start a well-formed placeholder (e.g.
+//! `{ print $1 }` in an awk script) are kept as literal text, and
+//! placeholders the caller does not know are rendered verbatim, so shell
+//! snippets keep working inside hook commands. Hook commands are rendered
+//! with [`render_shell`], which escapes every value for the shell.
+
+use std::fmt;
+
+/// Error produced when a template is syntactically invalid.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Error {
+    /// Byte offset into the template where the problem was found.
+    pub offset: usize,
+    pub message: String,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (at byte {})", self.message, self.offset)
+    }
+}
+
+impl std::error::Error for Error {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Filter {
+    Default(String),
+    Truncate(usize),
+    Lower,
+    Upper,
+}
+
+impl Filter {
+    fn apply(&self, value: String) -> String {
+        match self {
+            Filter::Default(fallback) if value.is_empty() => fallback.clone(),
+            Filter::Default(_) => value,
+            Filter::Truncate(max) => {
+                if value.chars().count() <= *max {
+                    value
+                } else if *max == 0 {
+                    String::new()
+                } else {
+                    let mut out: String = value.chars().take(max - 1).collect();
+                    out.push('…');
+                    out
+                }
+            }
+            Filter::Lower => value.to_lowercase(),
+            Filter::Upper => value.to_uppercase(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Node {
+    Text(String),
+    Var {
+        name: String,
+        filters: Vec<Filter>,
+        /// Original source text, emitted when the placeholder is unknown.
+        raw: String,
+    },
+    Section {
+        name: String,
+        body: Vec<Node>,
+    },
+}
+
+/// A parsed template.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+    nodes: Vec<Node>,
+    /// Byte offsets of `}}` with no `{{` to close, kept literally.
+    unmatched: Vec<usize>,
+}
+
+impl Template {
+    pub fn parse(src: &str) -> Result<Self, Error> {
+        let mut parser = Parser {
+            src,
+            pos: 0,
+            open_escapes: 0,
+            unmatched: Vec::new(),
+        };
+        let nodes = parser.parse_nodes(None)?;
+        Ok(Self {
+            nodes,
+            unmatched: parser.unmatched,
+        })
+    }
+
+    /// Byte offsets of every `}}` that closes no `{{`. They are rendered as
+    /// two braces, but may have been meant as an escaped `}`.
+    pub fn unmatched_braces(&self) -> &[usize] {
+        &self.unmatched
+    }
+
+    /// Names of all placeholders referenced by the template, in order of
+    /// first appearance.
+    pub fn placeholders(&self) -> Vec<&str> {
+        fn walk<'a>(nodes: &'a [Node], out: &mut Vec<&'a str>) {
+            for node in nodes {
+                match node {
+                    Node::Text(_) => {}
+                    Node::Var { name, .. } => {
+                        if !out.contains(&name.as_str()) {
+                            out.push(name);
+                        }
+                    }
+                    Node::Section { name, body } => {
+                        if !out.contains(&name.as_str()) {
+                            out.push(name);
+                        }
+                        walk(body, out);
+                    }
+                }
+            }
+        }
+        let mut out = Vec::new();
+        walk(&self.nodes, &mut out);
+        out
+    }
+
+    /// Render the template. `lookup` returns `None` for placeholders it does
+    /// not know, which are then emitted verbatim.
+    pub fn render(&self, lookup: &mut dyn FnMut(&str) -> Option<String>) -> String {
+        let mut out = String::new();
+        render_nodes(&self.nodes, lookup, &|v| v, &mut out);
+        out
+    }
+
+    /// Render the template as a shell command: like [`Template::render`],
+    /// with every value passed through [`shell_escape`].
+    pub fn render_shell(&self, lookup: &mut dyn FnMut(&str) -> Option<String>) -> String {
+        let mut out = String::new();
+        render_nodes(&self.nodes, lookup, &|v| shell_escape(&v), &mut out);
+        out
+    }
+}
+
+fn render_nodes(
+    nodes: &[Node],
+    lookup: &mut dyn FnMut(&str) -> Option<String>,
+    escape: &dyn Fn(String) -> String,
+    out: &mut String,
+) {
+    for node in nodes {
+        match node {
+            Node::Text(text) => out.push_str(text),
+            Node::Var { name, filters, raw } => match lookup(name) {
+                Some(value) => {
+                    let value = filters.iter().fold(value, |v, f| f.apply(v));
+                    out.push_str(&escape(value));
+                }
+                None => out.push_str(raw),
+            },
+            Node::Section { name, body } => {
+                if lookup(name).is_some_and(|v| !v.is_empty()) {
+                    render_nodes(body, lookup, escape, out);
+                }
+            }
+        }
+    }
+}
+
+/// Escape `value` so the shell reads it as literal text: every ASCII
+/// character other than letters, digits and `_-.,:/@%+=` gets a backslash
+/// (like bash's `printf %q`), control characters become spaces, and an
+/// empty value becomes `''`. Unlike wrapping it in quotes, this can't be
+/// undone by quotes around the placeholder in the command: inside them the
+/// value may show stray backslashes, but it never runs.
+pub fn shell_escape(value: &str) -> String {
+    if value.is_empty() {
+        return "''".to_string();
+    }
+    let mut out = String::with_capacity(value.len() * 2);
+    for c in value.chars() {
+        if c.is_control() {
+            out.push_str("\\ ");
+            continue;
+        }
+        if c.is_ascii() && !c.is_ascii_alphanumeric() && !"_-.,:/@%+=".contains(c) {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// Parse and render `src` in one go. Invalid templates are returned
+/// unchanged so a typo never swallows a notification or hook.
+pub fn render(src: &str, lookup: &mut dyn FnMut(&str) -> Option<String>) -> String {
+    match Template::parse(src) {
+        Ok(t) => t.render(lookup),
+        Err(_) => src.to_string(),
+    }
+}
+
+/// Like [`render`], for a hook command run by `sh -c`. An invalid template
+/// renders as nothing, since its placeholders were never escaped.
+pub fn render_shell(src: &str, lookup: &mut dyn FnMut(&str) -> Option<String>) -> String {
+    Template::parse(src)
+        .map(|t| t.render_shell(lookup))
+        .unwrap_or_default()
+}
+
+struct Parser<'a> {
+    src: &'a str,
+    pos: usize,
+    /// `{{` escapes not yet closed by a `}}`.
+    open_escapes: usize,
+    unmatched: Vec<usize>,
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '-'
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.src[self.pos..]
+    }
+
+    fn error(&self, offset: usize, message: impl Into<String>) -> Error {
+        Error {
+            offset,
+            message: message.into(),
+        }
+    }
+
+    /// Parse nodes until end of input, or until `{/}` when inside the
+    /// section opened at `section_start`.
+    fn parse_nodes(&mut self, section_start: Option<usize>) -> Result<Vec<Node>, Error> {
+        let mut nodes = Vec::new();
+        let mut text = String::new();
+
+        while let Some(c) = self.rest().chars().next() {
+            let start = self.pos;
+            if self.rest().starts_with("{{") {
+                text.push('{');
+                self.open_escapes += 1;
+                self.pos += 2;
+            } else if self.rest().starts_with("}}") {
+                if self.open_escapes > 0 {
+                    text.push('}');
+                    self.open_escapes -= 1;
+                } else {
+                    text.push_str("}}");
+                    self.unmatched.push(start);
+                }
+                self.pos += 2;
+            } else if self.rest().starts_with("${") {
+                // Shell parameter expansion, never a placeholder.
+                text.push_str("${");
+                self.pos += 2;
+            } else if self.rest().starts_with("{/}") {
+                self.pos += 3;
+                if section_start.is_none() {
+                    return Err(self.error(start, "'{/}' without an open section"));
+                }
+                if !text.is_empty() {
+                    nodes.push(Node::Text(std::mem::take(&mut text)));
+                }
+                return Ok(nodes);
+            } else if let Some(node) = self.parse_tag()? {
+                if !text.is_empty() {
+                    nodes.push(Node::Text(std::mem::take(&mut text)));
+                }
+                nodes.push(node);
+            } else {
+                text.push(c);
+                self.pos += c.len_utf8();
+            }
+        }
+
+        if let Some(start) = section_start {
+            return Err(self.error(start, "section is never closed with '{/}'"));
+        }
+        if !text.is_empty() {
+            nodes.push(Node::Text(text));
+        }
+        Ok(nodes)
+    }
+
+    /// Try to parse `{name...}` or `{?name}` at the current position.
+    /// Returns `Ok(None)` (consuming nothing) if the text is not a tag.
+    fn parse_tag(&mut self) -> Result<Option<Node>, Error> {
+        let start = self.pos;
+        let rest = self.rest();
+        let Some(inner) = rest.strip_prefix('{') else {
+            return Ok(None);
+        };
+
+        let (is_section, inner) = match inner.strip_prefix('?') {
+            Some(after) => (true, after),
+            None => (false, inner),
+        };
+        let name_len = inner.find(|c: char| !is_name_char(c)).unwrap_or(inner.len());
+        if name_len == 0 {
+            return Ok(None);
+        }
+        let name = &inner[..name_len];
+        let after_name = &inner[name_len..];
+
+        if is_section {
+            if !after_name.starts_with('}') {
+                return Ok(None);
+            }
+            self.pos = start + 1 + 1 + name_len + 1;
+            let body = self.parse_nodes(Some(start))?;
+            return Ok(Some(Node::Section {
+                name: name.to_string(),
+                body,
+            }));
+        }
+
+        if after_name.starts_with('}') {
+            self.pos = start + 1 + name_len + 1;
+            return Ok(Some(Node::Var {
+                name: name.to_string(),
+                filters: Vec::new(),
+                raw: self.src[start..self.pos].to_string(),
+            }));
+        }
+        if !after_name.starts_with('|') {
+            return Ok(None);
+        }
+
+        self.pos = start + 1 + name_len;
+        let mut filters = Vec::new();
+        while self.rest().starts_with('|') {
+            self.pos += 1;
+            filters.push(self.parse_filter()?);
+        }
+        if !self.rest().starts_with('}') {
+            return Err(self.error(start, format!("unterminated placeholder '{{{name}'")));
+        }
+        self.pos += 1;
+        Ok(Some(Node::Var {
+            name: name.to_string(),
+            filters,
+            raw: self.src[start..self.pos].to_string(),
+        }))
+    }
+
+    fn parse_filter(&mut self) -> Result<Filter, Error> {
+        let start = self.pos;
+        let rest = self.rest();
+        let len = rest.find(|c: char| !is_name_char(c)).unwrap_or(rest.len());
+        let name = &rest[..len];
+        self.pos += len;
+
+        let arg = if self.rest().starts_with(':') {
+            self.pos += 1;
+            Some(self.parse_arg()?)
+        } else {
+            None
+        };
+
+        match (name, arg) {
+            ("default", Some(arg)) => Ok(Filter::Default(arg)),
+            ("truncate", Some(arg)) => arg
+                .parse()
+                .map(Filter::Truncate)
+                .map_err(|_| self.error(start, format!("truncate needs a number, got '{arg}'"))),
+            ("lower", None) => Ok(Filter::Lower),
+            ("upper", None) => Ok(Filter::Upper),
+            ("default" | "truncate", None) => {
+                Err(self.error(start, format!("filter '{name}' needs an argument")))
+            }
+            ("lower" | "upper", Some(_)) => {
+                Err(self.error(start, format!("filter '{name}' takes no argument")))
+            }
+            _ => Err(self.error(start, format!("unknown filter '{name}'"))),
+        }
+    }
+
+    /// Parse a filter argument: either a double-quoted string (with `\"`
+    /// and `\\` escapes) or a bare run of characters up to `|` or `}`.
+    fn parse_arg(&mut self) -> Result<String, Error> {
+        let start = self.pos;
+        if !self.rest().starts_with('"') {
+            let rest = self.rest();
+            let len = rest.find(['|', '}']).unwrap_or(rest.len());
+            self.pos += len;
+            return Ok(rest[..len].to_string());
+        }
+
+        self.pos += 1;
+        let mut out = String::new();
+        let mut chars = self.rest().chars();
+        while let Some(c) = chars.next() {
+            self.pos += c.len_utf8();
+            match c {
+                '"' => return Ok(out),
+                '\\' => {
+                    let Some(escaped) = chars.next() else { break };
+                    self.pos += escaped.len_utf8();
+                    out.push(escaped);
+                }
+                c => out.push(c),
+            }
+        }
+        Err(self.error(start, "unterminated quoted argument"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(name: &str) -> Option<String> {
+        match name {
+            "branch" => Some("main".to_string()),
+            "status" => Some("WAITING".to_string()),
+            "empty" => Some(String::new()),
+            "body" => Some("a fairly long notification body".to_string()),
+            _ => None,
+        }
+    }
+
+    fn render_str(src: &str) -> String {
+        Template::parse(src).unwrap().render(&mut vars)
+    }
+
+    #[test]
+    fn plain_placeholders() {
+        assert_eq!(render_str("Done - {branch}"), "Done - main");
+        assert_eq!(render_str("no placeholders"), "no placeholders");
+    }
+
+    #[test]
+    fn filters() {
+        assert_eq!(render_str("{status|lower}"), "waiting");
+        assert_eq!(render_str("{branch|upper}"), "MAIN");
+        assert_eq!(render_str(r#"{empty|default:"none"}"#), "none");
+        assert_eq!(render_str("{branch|default:x}"), "main");
+        assert_eq!(render_str("{body|truncate:8}"), "a fairl…");
+        assert_eq!(render_str("{body|truncate:8|upper}"), "A FAIRL…");
+    }
+
+    #[test]
+    fn quoted_argument_escapes() {
+        assert_eq!(render_str(r#"{empty|default:"a \"b\" | {c}"}"#), r#"a "b" | {c}"#);
+    }
+
+    #[test]
+    fn conditional_sections() {
+        assert_eq!(render_str("Done{?branch} on {branch}{/}"), "Done on main");
+        assert_eq!(render_str("Done{?empty} on {empty}{/}"), "Done");
+        assert_eq!(render_str("Done{?missing} on {missing}{/}"), "Done");
+        assert_eq!(render_str("{?branch}[{?empty}x{/}{status}]{/}"), "[WAITING]");
+    }
+
+    #[test]
+    fn escaping_and_literals() {
+        assert_eq!(render_str("{{branch}}"), "{branch}");
+        assert_eq!(render_str("echo ${HOME}"), "echo ${HOME}");
+        assert_eq!(render_str("echo ${branch}"), "echo ${branch}");
+        assert_eq!(render_str("awk '{ print $1 }'"), "awk '{ print $1 }'");
+        assert_eq!(render_str("{unknown|lower}"), "{unknown|lower}");
+        assert_eq!(render_str("{{{branch}}}"), "{main}");
+    }
+
+    #[test]
+    fn unmatched_closing_braces_are_kept() {
+        let hook = "jq '{a:{b:1}}' > {branch}.json";
+        assert_eq!(render_shell(hook, &mut vars), "jq '{a:{b:1}}' > main.json");
+        assert_eq!(Template::parse(hook).unwrap().unmatched_braces(), [11]);
+        assert!(Template::parse("{{branch}}").unwrap().unmatched_braces().is_empty());
+    }
+
+    #[test]
+    fn placeholders_are_listed_once() {
+        let t = Template::parse("{a} {?b}{a|upper}{c}{/}").unwrap();
+        assert_eq!(t.placeholders(), vec!["a", "b", "c"]);
+    }
+
+    #[test]
+    fn syntax_errors() {
+        assert!(Template::parse("{a|bogus}").is_err());
+        assert!(Template::parse("{a|truncate:x}").is_err());
+        assert!(Template::parse("{a|default}").is_err());
+        assert!(Template::parse(r#"{a|default:"x}"#).is_err());
+        assert!(Template::parse("{?a} never closed").is_err());
+        assert!(Template::parse("stray {/}").is_err());
+    }
+
+    #[test]
+    fn invalid_template_renders_verbatim() {
+        assert_eq!(render("{?branch} oops", &mut vars), "{?branch} oops");
+        assert_eq!(render_shell("{?branch} oops", &mut vars), "");
+    }
+
+    #[test]
+    fn shell_values_are_escaped() {
+        let mut lookup = |name: &str| match name {
+            "branch" => Some("$(touch pwned)".to_string()),
+            "error" => Some("it's `bad`; rm -rf ~\nnext".to_string()),
+            "empty" => Some(String::new()),
+            _ => None,
+        };
+        let t = Template::parse("echo {branch} {error} {empty} ${HOME} {unknown}").unwrap();
+        assert_eq!(
+            t.render_shell(&mut lookup),
+            r"echo \$\(touch\ pwned\) it\'s\ \`bad\`\;\ rm\ -rf\ \~\ next '' ${HOME} {unknown}"
+        );
+        assert_eq!(shell_escape("main-2.0/fix_x"), "main-2.0/fix_x");
+        assert_eq!(shell_escape("…é"), "…é");
+    }
+}
@@ -0,0 +1,200 @@
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Escape sequences for the outer terminal, waiting to be written by the
+/// stdout relay. They can't be written directly: the relay owns stdout and
+/// must not interleave them with a half-written sequence from the agent.
+static PENDING: Mutex<Vec<u8>> = Mutex::new(Vec::new());
+
+fn queue(seq: &[u8]) {
+    if let Ok(mut pending) = PENDING.lock() {
+        pending.extend_from_slice(seq);
+    }
+}
+
+/// Take every queued sequence, leaving the queue empty.
+pub fn take_pending() -> Vec<u8> {
+    PENDING.lock().map(|mut p| std::mem::take(&mut *p)).unwrap_or_default()
+}
+
+/// Queue an OSC 1337 `SetUserVar` (WezTerm, iTerm2, kitty), so tab bars can
+/// show it. An empty value clears the variable.
+pub fn set_user_var(name: &str, value: &str) {
+    queue(format!("\x1b]1337;SetUserVar={name}={}\x07", base64(value.as_bytes())).as_bytes());
+}
+
+/// Whether the terminal's own title was pushed onto its title stack, to be
+/// restored by [`restore_title`].
+static TITLE_SAVED: AtomicBool = AtomicBool::new(false);
+
+/// Queue an OSC 2 window title. Control characters are dropped so the
+/// title can't end the sequence early.
+///
+/// The first title after startup (or after [`restore_title`]) saves the
+/// terminal's current one first (XTWINOPS `CSI 22;0t`).
+pub fn set_title(title: &str) {
+    if !TITLE_SAVED.swap(true, Ordering::Relaxed) {
+        queue(b"\x1b[22;0t");
+    }
+    let title: String = title.chars().filter(|c| !c.is_control()).collect();
+    queue(format!("\x1b]2;{title}\x07").as_bytes());
+}
+
+/// Queue a restore of the title saved by [`set_title`] (XTWINOPS
+/// `CSI 23;0t`). Does nothing if no title was set.
+pub fn restore_title() {
+    if TITLE_SAVED.swap(false, Ordering::Relaxed) {
+        queue(b"\x1b[23;0t");
+    }
+}
+
+/// Tracks the escape sequence or UTF-8 character the output is in the
+/// middle of, across chunks, to find where a sequence of our own can be
+/// inserted.
+#[derive(Debug, Default)]
+pub struct Boundary {
+    state: State,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum State {
+    #[default]
+    Ground,
+    /// Continuation bytes still expected for a UTF-8 character
+    Utf8(u8),
+    /// After ESC
+    Escape,
+    /// ESC followed by a byte that takes one more (SS3 `ESC O x`, charset
+    /// selection `ESC ( x`)
+    EscapeArg,
+    /// CSI, up to its final byte
+    Csi,
+    /// OSC / DCS / APC / PM / SOS string, up to BEL or ST (`ESC \`)
+    String,
+    /// ESC inside a string, possibly the start of ST
+    StringEscape,
+}
+
+impl Boundary {
+    /// Advance over `chunk`, the next bytes written to the terminal.
+    pub fn feed(&mut self, chunk: &[u8]) {
+        for &b in chunk {
+            self.state = match (self.state, b) {
+                // CAN and SUB abort any sequence
+                (_, 0x18 | 0x1a) => State::Ground,
+                (State::Ground | State::Utf8(_), 0x1b) => State::Escape,
+                (State::Ground | State::Utf8(_), 0xc0..=0xdf) => State::Utf8(1),
+                (State::Ground | State::Utf8(_), 0xe0..=0xef) => State::Utf8(2),
+                (State::Ground | State::Utf8(_), 0xf0..=0xf7) => State::Utf8(3),
+                (State::Utf8(n), 0x80..=0xbf) if n > 1 => State::Utf8(n - 1),
+                (State::Ground | State::Utf8(_), _) => State::Ground,
+                (State::Escape, b'[') => State::Csi,
+                (State::Escape, b']' | b'P' | b'_' | b'^' | b'X') => State::String,
+                (State::Escape, b'O' | b'(' | b')') => State::EscapeArg,
+                (State::Escape, 0x1b) => State::Escape,
+                (State::Escape | State::EscapeArg, _) => State::Ground,
+                (State::Csi, 0x40..=0x7e) => State::Ground,
+                (State::Csi, 0x1b) => State::Escape,
+                (State::Csi, _) => State::Csi,
+                (State::String, 0x07) => State::Ground,
+                (State::String | State::StringEscape, 0x1b) => State::StringEscape,
+                (State::StringEscape, b'\\') => State::Ground,
+                (State::String | State::StringEscape, _) => State::String,
+            };
+        }
+    }
+
+    /// Whether the output so far ends outside any escape sequence and
+    /// UTF-8 character.
+    pub fn at_boundary(&self) -> bool {
+        self.state == State::Ground
+    }
+}
+
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for group in data.chunks(3) {
+        let b = [group[0], *group.get(1).unwrap_or(&0), *group.get(2).unwrap_or(&0)];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        for i in 0..4 {
+            if i <= group.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base64_padding() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64("WAITING ✓".as_bytes()), "V0FJVElORyDinJM=");
+    }
+
+    fn at_boundary(chunk: &[u8]) -> bool {
+        let mut boundary = Boundary::default();
+        boundary.feed(chunk);
+        boundary.at_boundary()
+    }
+
+    #[test]
+    fn boundaries() {
+        assert!(at_boundary(b"plain text"));
+        assert!(at_boundary(b"\x1b[1;32mgreen\x1b[0m"));
+        assert!(at_boundary(b"\x1b]0;title\x07"));
+        assert!(at_boundary(b"\x1b]8;;http://x\x1b\\"));
+        assert!(at_boundary("é ✓".as_bytes()));
+        assert!(!at_boundary(b"text\x1b"));
+        assert!(!at_boundary(b"text\x1b[1;3"));
+        assert!(!at_boundary(b"\x1b]0;tit"));
+        assert!(!at_boundary(b"\x1b]0;a\x1b[1mb"));
+        assert!(!at_boundary(b"\x1bO"));
+        assert!(!at_boundary(&"é".as_bytes()[..1]));
+    }
+
+    #[test]
+    fn sequences_split_across_chunks() {
+        let mut boundary = Boundary::default();
+        boundary.feed(b"\x1b]0;ti");
+        assert!(!boundary.at_boundary());
+        // No ESC in this chunk, but it is still inside the OSC
+        boundary.feed(b"tle");
+        assert!(!boundary.at_boundary());
+        boundary.feed(b"\x07text");
+        assert!(boundary.at_boundary());
+
+        boundary.feed(b"\x1b]8;;http://x\x1b");
+        assert!(!boundary.at_boundary());
+        boundary.feed(b"\\");
+        assert!(boundary.at_boundary());
+
+        boundary.feed(&"✓".as_bytes()[..2]);
+        assert!(!boundary.at_boundary());
+        boundary.feed(&"✓".as_bytes()[2..]);
+        assert!(boundary.at_boundary());
+    }
+
+    #[test]
+    fn title_is_saved_and_restored() {
+        take_pending();
+        set_title("a\x07b\nc");
+        set_user_var("ai-agent-status", "IDLE");
+        set_title("d");
+        restore_title();
+        restore_title();
+        assert_eq!(
+            take_pending(),
+            b"\x1b[22;0t\x1b]2;abc\x07\x1b]1337;SetUserVar=ai-agent-status=SURMRQ==\x07\x1b]2;d\x07\x1b[23;0t"
+        );
+    }
+}
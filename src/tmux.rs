@@ -0,0 +1,295 @@
+use crate::log;
+use std::io::{self, BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::{mpsc, LazyLock, OnceLock};
+use std::time::Duration;
+
+/// The tmux pane this wrapper runs in, from `$TMUX_PANE` at startup.
+/// `None` outside tmux, in which case options fall back to the current
+/// window.
+static PANE: LazyLock<Option<String>> =
+    LazyLock::new(|| std::env::var("TMUX_PANE").ok().filter(|p| !p.is_empty()));
+
+pub fn pane() -> Option<&'static str> {
+    PANE.as_deref()
+}
+
+/// Quote an argument for the tmux command parser.
+fn quote(arg: &str) -> String {
+    let mut out = String::with_capacity(arg.len() + 2);
+    out.push('"');
+    for c in arg.chars() {
+        match c {
+            '"' | '\\' | '$' => {
+                out.push('\\');
+                out.push(c);
+            }
+            '\n' => out.push_str("\\n"),
+            _ => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Read the next `%begin` .. `%end`/`%error` reply block, skipping any
+/// notifications before it.
+fn read_reply(reader: &mut impl BufRead) -> io::Result<Result<String, String>> {
+    let mut begin = None;
+    let mut output = String::new();
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        let line = line.trim_end_matches(['\r', '\n']);
+        let Some(args) = &begin else {
+            begin = line.strip_prefix("%begin ").map(str::to_string);
+            continue;
+        };
+        // The closing line repeats the arguments of `%begin`, which tells
+        // it apart from command output that happens to start with `%end`.
+        if line.strip_prefix("%end ") == Some(args) {
+            return Ok(Ok(output));
+        }
+        if line.strip_prefix("%error ") == Some(args) {
+            return Ok(Err(output));
+        }
+        output.push_str(line);
+        output.push('\n');
+    }
+}
+
+/// How long to wait for tmux to answer a command before giving up on the
+/// control client.
+const REPLY_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Commands waiting for the worker thread. When tmux falls this far
+/// behind, further option updates are dropped rather than queued.
+const QUEUE_LEN: usize = 256;
+
+/// A control-mode client (`tmux -C`) attached to our session, so commands
+/// don't each need a new `tmux` process. A reader thread turns its output
+/// into replies, so waiting for one can time out.
+struct Control {
+    child: Child,
+    stdin: ChildStdin,
+    replies: mpsc::Receiver<io::Result<Result<String, String>>>,
+}
+
+impl Control {
+    fn connect() -> Option<Self> {
+        let pane = pane()?;
+        // `no-output` keeps pane output off the pipe; `ignore-size` keeps
+        // this client from resizing the session's windows.
+        let mut child = Command::new("tmux")
+            .args(["-C", "attach-session", "-t", pane, "-f", "no-output,ignore-size"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| log::info(format!("tmux control mode: cannot start: {e}")))
+            .ok()?;
+        let stdin = child.stdin.take()?;
+        let mut stdout = BufReader::new(child.stdout.take()?);
+        let (tx, replies) = mpsc::channel();
+        std::thread::spawn(move || {
+            loop {
+                let reply = read_reply(&mut stdout);
+                let done = reply.is_err();
+                if tx.send(reply).is_err() || done {
+                    return;
+                }
+            }
+        });
+        let control = Self {
+            child,
+            stdin,
+            replies,
+        };
+        // tmux answers the attach itself with a reply block first
+        match control.reply() {
+            Ok(Ok(_)) => {
+                log::info("tmux control mode: connected");
+                Some(control)
+            }
+            Ok(Err(e)) => {
+                log::info(format!("tmux control mode: unavailable: {}", e.trim()));
+                None
+            }
+            Err(e) => {
+                log::info(format!("tmux control mode: unavailable: {e}"));
+                None
+            }
+        }
+    }
+
+    /// Send one command and wait for its reply: the command's output, or
+    /// its error message.
+    fn command(&mut self, args: &[&str]) -> io::Result<Result<String, String>> {
+        let line: Vec<String> = args.iter().map(|a| quote(a)).collect();
+        writeln!(self.stdin, "{}", line.join(" "))?;
+        self.stdin.flush()?;
+        self.reply()
+    }
+
+    fn reply(&self) -> io::Result<Result<String, String>> {
+        match self.replies.recv_timeout(REPLY_TIMEOUT) {
+            Ok(reply) => reply,
+            Err(mpsc::RecvTimeoutError::Timeout) => Err(io::ErrorKind::TimedOut.into()),
+            Err(mpsc::RecvTimeoutError::Disconnected) => Err(io::ErrorKind::UnexpectedEof.into()),
+        }
+    }
+}
+
+impl Drop for Control {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Runs tmux commands, in the order they were queued, on the worker
+/// thread: over the control client while it works, otherwise by spawning
+/// `tmux`.
+pub struct Worker {
+    /// Connected on first use; `None` once control mode turned out to be
+    /// unavailable or the client died or hung.
+    control: Option<Option<Control>>,
+}
+
+impl Worker {
+    /// Run a tmux command and return its stdout, or `None` if it failed.
+    pub fn output(&mut self, args: &[&str]) -> Option<String> {
+        if let Some(client) = self.control.get_or_insert_with(Control::connect) {
+            match client.command(args) {
+                Ok(reply) => return reply.ok(),
+                Err(e) => {
+                    log::info(format!("tmux control mode: lost connection ({e}), spawning tmux instead"));
+                    self.control = Some(None);
+                }
+            }
+        }
+        spawn_output(args)
+    }
+
+    /// Run a tmux command, discarding its output.
+    pub fn run(&mut self, args: &[&str]) {
+        let _ = self.output(args);
+    }
+}
+
+type Job = Box<dyn FnOnce(&mut Worker) + Send>;
+
+/// Queue of the worker thread, started on first use. `None` if the thread
+/// could not be started.
+static QUEUE: OnceLock<Option<mpsc::SyncSender<Job>>> = OnceLock::new();
+
+fn start_worker() -> Option<mpsc::SyncSender<Job>> {
+    let (tx, rx) = mpsc::sync_channel::<Job>(QUEUE_LEN);
+    let spawned = std::thread::Builder::new().name("tmux".to_string()).spawn(move || {
+        let mut worker = Worker { control: None };
+        for job in rx {
+            job(&mut worker);
+        }
+    });
+    spawned.ok().map(|_| tx)
+}
+
+/// Run `job` on the worker thread, after everything queued before it,
+/// without waiting. Use this for commands that depend on each other, e.g.
+/// reading options and then setting one from them.
+pub fn queue(job: impl FnOnce(&mut Worker) + Send + 'static) {
+    let Some(queue) = QUEUE.get_or_init(start_worker) else {
+        job(&mut Worker { control: Some(None) });
+        return;
+    };
+    if let Err(mpsc::TrySendError::Full(_)) = queue.try_send(Box::new(job)) {
+        log::info("tmux: not keeping up, dropping an update");
+    }
+}
+
+/// Run a tmux command and return its stdout, or `None` if it failed.
+///
+/// Waits for the worker thread; if it doesn't answer in time (tmux is hung
+/// or far behind), spawns `tmux` instead.
+pub fn output(args: &[&str]) -> Option<String> {
+    let (tx, rx) = mpsc::channel();
+    let owned: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+    queue(move |tmux| {
+        let args: Vec<&str> = owned.iter().map(String::as_str).collect();
+        let _ = tx.send(tmux.output(&args));
+    });
+    match rx.recv_timeout(REPLY_TIMEOUT * 2) {
+        Ok(output) => output,
+        Err(_) => spawn_output(args),
+    }
+}
+
+/// Run a tmux command in the background, discarding its output. Never
+/// blocks, so it is safe to call for every chunk of agent output.
+pub fn run(args: &[&str]) {
+    let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+    queue(move |tmux| {
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        tmux.run(&args);
+    });
+}
+
+/// Wait (up to a few seconds) until every command queued so far has run,
+/// e.g. before exiting.
+pub fn flush() {
+    if QUEUE.get().is_none_or(Option::is_none) {
+        return;
+    }
+    let (tx, rx) = mpsc::channel();
+    queue(move |_| {
+        let _ = tx.send(());
+    });
+    let _ = rx.recv_timeout(REPLY_TIMEOUT * 2);
+}
+
+fn spawn_output(args: &[&str]) -> Option<String> {
+    Command::new("tmux")
+        .args(args)
+        .stderr(Stdio::null())
+        .output()
+        .ok()
+        .filter(|o| o.status.success())
+        .map(|o| String::from_utf8_lossy(&o.stdout).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quote_escapes_parser_specials() {
+        assert_eq!(quote("@ai-agent-status"), r#""@ai-agent-status""#);
+        assert_eq!(quote(r#"say "hi" $HOME \o/"#), r#""say \"hi\" \$HOME \\o/""#);
+        assert_eq!(quote("#S\t#W;x\ny"), "\"#S\t#W;x\\ny\"");
+    }
+
+    #[test]
+    fn reply_blocks_skip_notifications() {
+        let stream = "%sessions-changed\n\
+                      %begin 1700000000 12 1\n\
+                      main\n\
+                      %end 0 0 0\n\
+                      %end 1700000000 12 1\n\
+                      %begin 1700000000 13 1\n\
+                      no such option\n\
+                      %error 1700000000 13 1\n";
+        let mut reader = stream.as_bytes();
+        assert_eq!(
+            read_reply(&mut reader).unwrap(),
+            Ok("main\n%end 0 0 0\n".to_string())
+        );
+        assert_eq!(
+            read_reply(&mut reader).unwrap(),
+            Err("no such option\n".to_string())
+        );
+        assert!(read_reply(&mut reader).is_err());
+    }
+}